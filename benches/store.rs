@@ -0,0 +1,119 @@
+// Timings for loading, listing and filtering a large list. Each scenario runs the
+// binary on a synthetic todo.json, so it measures what a user waits for, including
+// start-up and writing the output.
+//
+// `cargo bench --bench store` first checks every scenario against its budget and
+// fails if one is over, then hands them to criterion for detailed timings.
+
+use criterion::Criterion;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+// Items in the synthetic list
+const ITEMS: usize = 100_000;
+
+// Name, arguments and the most one run may take. The budgets leave room for slow CI
+// machines; see "Performance" in the README
+const SCENARIOS: [(&str, &[&str], Duration); 4] = [
+    (
+        "load",
+        &["list", "--all", "--count-only"],
+        Duration::from_millis(1500),
+    ),
+    ("list", &["list", "--all"], Duration::from_millis(3000)),
+    (
+        "filter",
+        &["list", "--where", "project:P7 due<now+4w"],
+        Duration::from_millis(1500),
+    ),
+    ("projects", &["projects"], Duration::from_millis(1500)),
+];
+
+// Runs taken for the budget check; the fastest one counts
+const BUDGET_RUNS: usize = 3;
+
+// A list with a mix of priorities, projects, contexts, tags and dates, a quarter done
+fn synthetic_store() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("todo-cli-bench-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Failed to create bench directory");
+
+    let items: Vec<serde_json::Value> = (0..ITEMS)
+        .map(|i| {
+            let month = 1 + i % 12;
+            let day = 1 + i % 28;
+            let priority = ['A', 'B', 'C'].get(i % 4);
+            let context = ["phone", "desk"].get(i % 3);
+            serde_json::json!({
+                "priority": priority,
+                "description": format!("Synthetic item {} with a few more words to wrap", i),
+                "context": context,
+                "project": format!("P{}", i % 50),
+                "tags": if i % 3 == 0 { vec!["review"] } else { vec![] },
+                "start_date": format!("2025/{:02}/{:02}", month, day),
+                "done_date": (i % 4 == 0).then_some("2025/12/01"),
+                "due_date": (i % 5 == 0).then(|| format!("2026/{:02}/{:02}", month, day)),
+            })
+        })
+        .collect();
+    fs::write(
+        dir.join("todo.json"),
+        serde_json::to_string_pretty(&items).expect("Failed to serialize the list"),
+    )
+    .expect("Failed to write the list");
+    dir
+}
+
+fn run(dir: &Path, args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_todo-cli"))
+        .current_dir(dir)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .expect("Failed to run todo-cli");
+    assert!(status.success(), "todo-cli {} failed", args.join(" "));
+}
+
+// The scenarios over budget, as messages
+fn over_budget(dir: &Path) -> Vec<String> {
+    let mut over = Vec::new();
+    for (name, args, budget) in SCENARIOS {
+        let fastest = (0..BUDGET_RUNS)
+            .map(|_| {
+                let start = Instant::now();
+                run(dir, args);
+                start.elapsed()
+            })
+            .min()
+            .unwrap_or_default();
+        println!("{:<10} {:>8.1?} (budget {:?})", name, fastest, budget);
+        if fastest > budget {
+            over.push(format!(
+                "{} took {:.1?}, over its {:?} budget",
+                name, fastest, budget
+            ));
+        }
+    }
+    over
+}
+
+fn main() {
+    let dir = synthetic_store();
+
+    let over = over_budget(&dir);
+    if !over.is_empty() {
+        let _ = fs::remove_dir_all(&dir);
+        panic!("{}", over.join("\n"));
+    }
+
+    let mut criterion = Criterion::default().sample_size(10).configure_from_args();
+    for (name, args, _) in SCENARIOS {
+        criterion.bench_function(name, |b| b.iter(|| run(&dir, args)));
+    }
+    criterion.final_summary();
+
+    let _ = fs::remove_dir_all(&dir);
+}
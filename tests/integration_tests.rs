@@ -1,12 +1,24 @@
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::fs;
+use std::path::PathBuf;
 use std::process::Command;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 const TEST_TODO_FILE: &str = "todo.json";
-
-// Global lock to ensure tests run serially
-static TEST_LOCK: Mutex<()> = Mutex::new(());
+const TEST_CONFIG_FILE: &str = "todo-config.toml";
+const TEST_LOCK_FILE: &str = "todo.json.lock";
+const TEST_META_FILE: &str = "todo.meta.json";
+const TEST_ARCHIVE_FILE: &str = "todo-archive.json";
+const TEST_STATE_FILE: &str = "todo.state.json";
+const TEST_HISTORY_FILE: &str = "todo.history.jsonl";
+
+static NEXT_TEST_DIR: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Each test runs in its own directory, so tests can run in parallel
+    static TEST_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct TodoItem {
@@ -22,13 +34,32 @@ struct TodoItem {
 }
 
 fn setup() {
-    // Remove test file if it exists
-    let _ = fs::remove_file(TEST_TODO_FILE);
+    // Start in a new, empty directory for this test
+    let dir = std::env::temp_dir().join(format!(
+        "todo-cli-test-{}-{}",
+        std::process::id(),
+        NEXT_TEST_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Failed to create test directory");
+    TEST_DIR.with(|test_dir| *test_dir.borrow_mut() = Some(dir));
 }
 
 fn teardown() {
-    // Clean up test file
-    let _ = fs::remove_file(TEST_TODO_FILE);
+    // Clean up the test's directory
+    if let Some(dir) = TEST_DIR.with(|test_dir| test_dir.borrow_mut().take()) {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+// The directory the current test runs the binary in
+fn test_dir() -> PathBuf {
+    TEST_DIR.with(|test_dir| test_dir.borrow().clone().expect("setup() was not called"))
+}
+
+// A file in the current test's directory
+fn test_path(name: impl AsRef<std::path::Path>) -> PathBuf {
+    test_dir().join(name)
 }
 
 fn get_binary_path() -> std::path::PathBuf {
@@ -61,6 +92,7 @@ fn get_binary_path() -> std::path::PathBuf {
 
 fn run_command(args: &[&str]) -> std::process::Output {
     Command::new(get_binary_path())
+        .current_dir(test_dir())
         .args(args)
         .output()
         .expect("Failed to execute command")
@@ -69,6 +101,7 @@ fn run_command(args: &[&str]) -> std::process::Output {
 fn run_command_with_input(args: &[&str], input: &str) -> std::process::Output {
     use std::io::Write;
     let mut child = Command::new(get_binary_path())
+        .current_dir(test_dir())
         .args(args)
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
@@ -76,10 +109,12 @@ fn run_command_with_input(args: &[&str], input: &str) -> std::process::Output {
         .spawn()
         .expect("Failed to spawn command");
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin
-            .write_all(input.as_bytes())
-            .expect("Failed to write to stdin");
+    // A command that fails before reading its input closes the pipe early
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(e) = stdin.write_all(input.as_bytes())
+        && e.kind() != std::io::ErrorKind::BrokenPipe
+    {
+        panic!("Failed to write to stdin: {}", e);
     }
 
     child
@@ -89,7 +124,7 @@ fn run_command_with_input(args: &[&str], input: &str) -> std::process::Output {
 
 fn create_test_file_with_todos(todos: Vec<TodoItem>) {
     let json = serde_json::to_string_pretty(&todos).expect("Failed to serialize todos");
-    fs::write(TEST_TODO_FILE, json).expect("Failed to write test file");
+    fs::write(test_path(TEST_TODO_FILE), json).expect("Failed to write test file");
 }
 
 fn make_todo(description: &str, priority: Option<char>, done_date: Option<&str>) -> TodoItem {
@@ -107,14 +142,13 @@ fn make_todo(description: &str, priority: Option<char>, done_date: Option<&str>)
 
 #[test]
 fn test_add_simple_todo() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     // Create file first
     run_command_with_input(&["add", "Buy milk"], "Y\n");
 
     // Verify file exists and contains the todo
-    let content = fs::read_to_string(TEST_TODO_FILE);
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE));
     if content.is_err() {
         teardown();
         panic!("Failed to read test file");
@@ -129,12 +163,11 @@ fn test_add_simple_todo() {
 
 #[test]
 fn test_add_todo_with_metadata() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     run_command_with_input(&["add", "Buy milk @shopping P:Personal T:urgent"], "Y\n");
 
-    let content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(content.contains("Buy milk"));
     assert!(content.contains("shopping"));
     assert!(content.contains("Personal"));
@@ -145,7 +178,6 @@ fn test_add_todo_with_metadata() {
 
 #[test]
 fn test_list_empty() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
     create_test_file_with_todos(vec![]);
 
@@ -159,7 +191,6 @@ fn test_list_empty() {
 
 #[test]
 fn test_list_filters_done_items() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![
@@ -197,7 +228,6 @@ fn test_list_filters_done_items() {
 
 #[test]
 fn test_list_all_shows_done_items() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![
@@ -235,7 +265,6 @@ fn test_list_all_shows_done_items() {
 
 #[test]
 fn test_list_priority_sorting() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![
@@ -294,7 +323,6 @@ fn test_list_priority_sorting() {
 
 #[test]
 fn test_set_priority() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
@@ -304,7 +332,7 @@ fn test_set_priority() {
 
     assert!(stdout.contains("Set priority"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("\"A\""));
     assert!(updated_content.contains("Buy milk"));
 
@@ -313,14 +341,13 @@ fn test_set_priority() {
 
 #[test]
 fn test_change_priority() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", Some('A'), None)]);
 
     run_command(&["pr", "b", "1"]);
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("\"B\""));
     assert!(!updated_content.contains("\"A\""));
 
@@ -329,7 +356,6 @@ fn test_change_priority() {
 
 #[test]
 fn test_clear_priority() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", Some('A'), None)]);
@@ -339,7 +365,7 @@ fn test_clear_priority() {
 
     assert!(stdout.contains("Cleared priority"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("null"));
     assert!(updated_content.contains("Buy milk"));
 
@@ -348,7 +374,6 @@ fn test_clear_priority() {
 
 #[test]
 fn test_mark_done() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
@@ -358,7 +383,7 @@ fn test_mark_done() {
 
     assert!(stdout.contains("marked as done"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("done_date"));
 
     teardown();
@@ -366,7 +391,6 @@ fn test_mark_done() {
 
 #[test]
 fn test_mark_done_cancelled() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
@@ -376,7 +400,7 @@ fn test_mark_done_cancelled() {
 
     assert!(stdout.contains("Cancelled"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("\"done_date\": null"));
 
     teardown();
@@ -384,7 +408,6 @@ fn test_mark_done_cancelled() {
 
 #[test]
 fn test_mark_done_already_done() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, Some("2025/11/30"))]);
@@ -399,7 +422,6 @@ fn test_mark_done_already_done() {
 
 #[test]
 fn test_mark_done_invalid_number() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
@@ -414,7 +436,6 @@ fn test_mark_done_invalid_number() {
 
 #[test]
 fn test_priority_invalid_number() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
@@ -429,14 +450,13 @@ fn test_priority_invalid_number() {
 
 #[test]
 fn test_lowercase_priority_converted() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
 
     run_command(&["pr", "c", "1"]);
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("\"C\""));
 
     teardown();
@@ -444,7 +464,6 @@ fn test_lowercase_priority_converted() {
 
 #[test]
 fn test_list_shows_line_numbers() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![
@@ -465,7 +484,6 @@ fn test_list_shows_line_numbers() {
 
 #[test]
 fn test_priority_with_done_item() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", Some('A'), Some("2025/11/30"))]);
@@ -481,7 +499,6 @@ fn test_priority_with_done_item() {
 
 #[test]
 fn test_projects_empty() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
@@ -496,7 +513,6 @@ fn test_projects_empty() {
 
 #[test]
 fn test_projects_single() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todo = TodoItem {
@@ -523,7 +539,6 @@ fn test_projects_single() {
 
 #[test]
 fn test_projects_multiple_unique() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![
@@ -581,7 +596,6 @@ fn test_projects_multiple_unique() {
 
 #[test]
 fn test_projects_with_duplicates() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![
@@ -633,7 +647,6 @@ fn test_projects_with_duplicates() {
 
 #[test]
 fn test_projects_includes_done_items() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![
@@ -677,22 +690,19 @@ const TEST_TXT_FILE: &str = "test_todo.txt";
 const TEST_OUTPUT_FILE: &str = "test_output.json";
 
 fn setup_convert() {
-    let _ = fs::remove_file(TEST_TXT_FILE);
-    let _ = fs::remove_file(TEST_OUTPUT_FILE);
+    setup();
 }
 
 fn teardown_convert() {
-    let _ = fs::remove_file(TEST_TXT_FILE);
-    let _ = fs::remove_file(TEST_OUTPUT_FILE);
+    teardown();
 }
 
 fn create_test_txt_file(content: &str) {
-    fs::write(TEST_TXT_FILE, content).expect("Failed to write test txt file");
+    fs::write(test_path(TEST_TXT_FILE), content).expect("Failed to write test txt file");
 }
 
 #[test]
 fn test_convert_simple() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file("Buy milk S:2025/11/29\n");
@@ -702,7 +712,7 @@ fn test_convert_simple() {
 
     assert!(stdout.contains("Converted 1 todo items"));
 
-    let json_content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let json_content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(json_content.contains("Buy milk"));
     assert!(json_content.contains("2025/11/29"));
 
@@ -711,14 +721,13 @@ fn test_convert_simple() {
 
 #[test]
 fn test_convert_with_priority() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file("(A) Important task S:2025/11/29\n");
 
     run_command(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE]);
 
-    let json_content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let json_content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(json_content.contains("\"priority\": \"A\""));
     assert!(json_content.contains("Important task"));
 
@@ -727,14 +736,13 @@ fn test_convert_with_priority() {
 
 #[test]
 fn test_convert_with_metadata() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file("Buy milk @shopping P:Personal T:urgent S:2025/11/29\n");
 
     run_command(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE]);
 
-    let json_content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let json_content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(json_content.contains("Buy milk"));
     assert!(json_content.contains("\"context\": \"shopping\""));
     assert!(json_content.contains("\"project\": \"Personal\""));
@@ -745,14 +753,13 @@ fn test_convert_with_metadata() {
 
 #[test]
 fn test_convert_with_done_date() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file("Completed task S:2025/11/28 D:2025/11/29\n");
 
     run_command(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE]);
 
-    let json_content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let json_content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(json_content.contains("Completed task"));
     assert!(json_content.contains("\"start_date\": \"2025/11/28\""));
     assert!(json_content.contains("\"done_date\": \"2025/11/29\""));
@@ -762,7 +769,6 @@ fn test_convert_with_done_date() {
 
 #[test]
 fn test_convert_multiple_items() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     let content = "Buy milk @shopping S:2025/11/29\n\
@@ -775,7 +781,7 @@ fn test_convert_multiple_items() {
 
     assert!(stdout.contains("Converted 3 todo items"));
 
-    let json_content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let json_content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(json_content.contains("Buy milk"));
     assert!(json_content.contains("Send email"));
     assert!(json_content.contains("Call dentist"));
@@ -785,7 +791,6 @@ fn test_convert_multiple_items() {
 
 #[test]
 fn test_convert_missing_input_file() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     let output = run_command(&["convert", "nonexistent.txt", "-o", TEST_OUTPUT_FILE]);
@@ -799,11 +804,10 @@ fn test_convert_missing_input_file() {
 
 #[test]
 fn test_convert_overwrite_cancelled() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file("Buy milk S:2025/11/29\n");
-    fs::write(TEST_OUTPUT_FILE, "existing content").unwrap();
+    fs::write(test_path(TEST_OUTPUT_FILE), "existing content").unwrap();
 
     let output = run_command_with_input(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE], "N\n");
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -811,7 +815,7 @@ fn test_convert_overwrite_cancelled() {
     assert!(stdout.contains("Cancelled"));
 
     // Verify original content preserved
-    let content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert_eq!(content, "existing content");
 
     teardown_convert();
@@ -819,11 +823,10 @@ fn test_convert_overwrite_cancelled() {
 
 #[test]
 fn test_convert_overwrite_confirmed() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file("Buy milk S:2025/11/29\n");
-    fs::write(TEST_OUTPUT_FILE, "existing content").unwrap();
+    fs::write(test_path(TEST_OUTPUT_FILE), "existing content").unwrap();
 
     let output = run_command_with_input(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE], "Y\n");
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -831,7 +834,7 @@ fn test_convert_overwrite_confirmed() {
     assert!(stdout.contains("Converted 1 todo items"));
 
     // Verify content was overwritten
-    let content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(content.contains("Buy milk"));
 
     teardown_convert();
@@ -839,7 +842,6 @@ fn test_convert_overwrite_confirmed() {
 
 #[test]
 fn test_convert_empty_lines_skipped() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     let content = "Buy milk S:2025/11/29\n\n\nSend email S:2025/11/28\n\n";
@@ -855,14 +857,13 @@ fn test_convert_empty_lines_skipped() {
 
 #[test]
 fn test_convert_multiple_tags() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file("Review code T:urgent T:backend T:review S:2025/11/29\n");
 
     run_command(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE]);
 
-    let json_content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let json_content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(json_content.contains("urgent"));
     assert!(json_content.contains("backend"));
     assert!(json_content.contains("review"));
@@ -872,14 +873,13 @@ fn test_convert_multiple_tags() {
 
 #[test]
 fn test_convert_lowercase_markers() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file("(b) Task @home p:personal t:quick s:2025/11/29 d:2025/11/30\n");
 
     run_command(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE]);
 
-    let json_content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let json_content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(json_content.contains("\"priority\": \"B\""));
     assert!(json_content.contains("\"context\": \"home\""));
     assert!(json_content.contains("\"project\": \"personal\""));
@@ -892,7 +892,6 @@ fn test_convert_lowercase_markers() {
 
 #[test]
 fn test_convert_complex_description() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup_convert();
 
     create_test_txt_file(
@@ -901,7 +900,7 @@ fn test_convert_complex_description() {
 
     run_command(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE]);
 
-    let json_content = fs::read_to_string(TEST_OUTPUT_FILE).unwrap();
+    let json_content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
     assert!(json_content.contains("Send email about the meeting tomorrow"));
 
     teardown_convert();
@@ -911,7 +910,6 @@ fn test_convert_complex_description() {
 
 #[test]
 fn test_edit_description() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Original task", None, None)]);
@@ -922,7 +920,7 @@ fn test_edit_description() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("Updated task"));
     assert!(!updated_content.contains("Original task"));
 
@@ -931,7 +929,6 @@ fn test_edit_description() {
 
 #[test]
 fn test_edit_priority() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
@@ -942,7 +939,7 @@ fn test_edit_priority() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("\"priority\": \"A\""));
     assert!(updated_content.contains("Buy milk"));
 
@@ -951,7 +948,6 @@ fn test_edit_priority() {
 
 #[test]
 fn test_edit_context_and_project() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![TodoItem {
@@ -972,7 +968,7 @@ fn test_edit_context_and_project() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("\"context\": \"work\""));
     assert!(updated_content.contains("\"project\": \"Website\""));
     assert!(updated_content.contains("Send email"));
@@ -982,7 +978,6 @@ fn test_edit_context_and_project() {
 
 #[test]
 fn test_edit_tags() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Review code", None, None)]);
@@ -993,7 +988,7 @@ fn test_edit_tags() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("\"urgent\""));
     assert!(updated_content.contains("\"important\""));
 
@@ -1002,7 +997,6 @@ fn test_edit_tags() {
 
 #[test]
 fn test_edit_clear_fields() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![TodoItem {
@@ -1023,7 +1017,7 @@ fn test_edit_clear_fields() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("Task with metadata"));
     assert!(updated_content.contains("\"priority\": null"));
     assert!(updated_content.contains("\"context\": null"));
@@ -1035,7 +1029,6 @@ fn test_edit_clear_fields() {
 
 #[test]
 fn test_edit_keep_current_values() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     let todos = vec![TodoItem {
@@ -1056,7 +1049,7 @@ fn test_edit_keep_current_values() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     // Content should be essentially the same (only formatting might differ)
     assert!(updated_content.contains("Original description"));
     assert!(updated_content.contains("\"B\""));
@@ -1069,7 +1062,6 @@ fn test_edit_keep_current_values() {
 
 #[test]
 fn test_edit_invalid_number() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Task 1", None, None)]);
@@ -1084,7 +1076,6 @@ fn test_edit_invalid_number() {
 
 #[test]
 fn test_edit_all_fields() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Old task", None, None)]);
@@ -1098,7 +1089,7 @@ fn test_edit_all_fields() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("New task"));
     assert!(updated_content.contains("\"C\""));
     assert!(updated_content.contains("office"));
@@ -1112,12 +1103,11 @@ fn test_edit_all_fields() {
 
 #[test]
 fn test_add_todo_with_absolute_due_date() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     run_command_with_input(&["add", "Task with due date Due:2026-06-15"], "Y\n");
 
-    let content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(content.contains("Task with due date"));
     assert!(content.contains("2026/06/15"));
     assert!(content.contains("due_date"));
@@ -1125,14 +1115,55 @@ fn test_add_todo_with_absolute_due_date() {
     teardown();
 }
 
+#[test]
+fn test_add_todo_with_start_done_and_estimate() {
+    setup();
+
+    run_command_with_input(
+        &["add", "Call Bob S:2025-12-01 D:2025-12-02 est:45m"],
+        "Y\n",
+    );
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(content.contains("\"description\": \"Call Bob\""));
+    assert!(content.contains("\"start_date\": \"2025/12/01\""));
+    assert!(content.contains("\"done_date\": \"2025/12/02\""));
+    assert!(content.contains("\"estimate\": 45"));
+
+    let output = run_command(&["list", "--all"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 S:2025/12/01 Call Bob Est:45m D:2025/12/02"));
+
+    teardown();
+}
+
+#[test]
+fn test_add_todo_rejects_invalid_dates() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+
+    let output = run_command(&["add", "Call Bob S:2025-13-01"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid start date '2025-13-01'"));
+
+    let output = run_command(&["add", "Call Bob Due:someday"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid due date 'someday'"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(!content.contains("Call Bob"));
+
+    teardown();
+}
+
 #[test]
 fn test_add_todo_with_relative_due_date() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     run_command_with_input(&["add", "Task due in 3 days Due:+3d"], "Y\n");
 
-    let content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(content.contains("Task due in 3 days"));
     assert!(content.contains("due_date"));
     // The actual date will be calculated, so we just check it exists
@@ -1142,7 +1173,6 @@ fn test_add_todo_with_relative_due_date() {
 
 #[test]
 fn test_list_shows_due_dates() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     run_command_with_input(&["add", "Task 1 Due:2026-01-10"], "Y\n");
@@ -1169,7 +1199,6 @@ fn test_list_shows_due_dates() {
 
 #[test]
 fn test_edit_due_date() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     create_test_file_with_todos(vec![make_todo("Task to edit", None, None)]);
@@ -1180,7 +1209,7 @@ fn test_edit_due_date() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     assert!(updated_content.contains("2026/07/15"));
     assert!(updated_content.contains("due_date"));
 
@@ -1189,7 +1218,6 @@ fn test_edit_due_date() {
 
 #[test]
 fn test_edit_clear_due_date() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     // First create a todo with a due date
@@ -1201,7 +1229,7 @@ fn test_edit_clear_due_date() {
 
     assert!(stdout.contains("updated successfully"));
 
-    let updated_content = fs::read_to_string(TEST_TODO_FILE).unwrap();
+    let updated_content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
     let todos: Vec<TodoItem> = serde_json::from_str(&updated_content).unwrap();
     assert!(todos[0].due_date.is_none());
 
@@ -1210,7 +1238,6 @@ fn test_edit_clear_due_date() {
 
 #[test]
 fn test_list_hide_waiting() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     // Add tasks with and without @WF context
@@ -1239,7 +1266,6 @@ fn test_list_hide_waiting() {
 
 #[test]
 fn test_list_hide_waiting_case_insensitive() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     // Add tasks with different case variations of @WF
@@ -1262,7 +1288,6 @@ fn test_list_hide_waiting_case_insensitive() {
 
 #[test]
 fn test_list_hide_waiting_with_no_results() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     // Add only waiting tasks
@@ -1280,7 +1305,6 @@ fn test_list_hide_waiting_with_no_results() {
 
 #[test]
 fn test_list_smart_sorting_priority() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     // Add tasks with different combinations of due dates and priorities
@@ -1337,7 +1361,6 @@ fn test_list_smart_sorting_priority() {
 
 #[test]
 fn test_list_smart_sorting_same_priority_different_due_dates() {
-    let _lock = TEST_LOCK.lock().unwrap();
     setup();
 
     // Add tasks with same priority but different due dates
@@ -1370,3 +1393,3764 @@ fn test_list_smart_sorting_same_priority_different_due_dates() {
 
     teardown();
 }
+
+// Merge conflict resolution tests
+
+fn create_conflicted_file(local_priority: &str, remote_priority: &str) {
+    let content = format!(
+        r#"[
+  {{
+<<<<<<< HEAD
+    "priority": "{}",
+=======
+    "priority": "{}",
+>>>>>>> other-machine
+    "description": "Buy milk",
+    "context": null,
+    "project": null,
+    "tags": [],
+    "start_date": "2025/11/29",
+    "done_date": null,
+    "due_date": null
+  }}
+]"#,
+        local_priority, remote_priority
+    );
+    fs::write(test_path(TEST_TODO_FILE), content).expect("Failed to write test file");
+}
+
+#[test]
+fn test_list_refuses_conflicted_file() {
+    setup();
+
+    create_conflicted_file("A", "B");
+
+    let output = run_command(&["list"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("todo-cli resolve"));
+
+    teardown();
+}
+
+#[test]
+fn test_resolve_prefer_remote() {
+    setup();
+
+    create_conflicted_file("A", "B");
+
+    let output = run_command(&["resolve", "--prefer", "remote"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Resolved 1 conflicting item(s)"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].priority, Some('B'));
+
+    teardown();
+}
+
+#[test]
+fn test_resolve_interactive_keep_local() {
+    setup();
+
+    create_conflicted_file("A", "B");
+
+    let output = run_command_with_input(&["resolve"], "l\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("LOCAL"));
+    assert!(stdout.contains("REMOTE"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[0].priority, Some('A'));
+
+    teardown();
+}
+
+#[test]
+fn test_resolve_interactive_merge_fields() {
+    setup();
+
+    create_conflicted_file("A", "B");
+
+    run_command_with_input(&["resolve"], "m\nr\n");
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[0].priority, Some('B'));
+
+    teardown();
+}
+
+// Standup report tests
+
+#[test]
+fn test_standup_markdown() {
+    setup();
+
+    let yesterday = (chrono::Local::now() - chrono::Duration::days(1))
+        .format("%Y/%m/%d")
+        .to_string();
+    let mut waiting = make_todo("Waiting on legal", None, None);
+    waiting.context = Some("WF".to_string());
+    create_test_file_with_todos(vec![
+        make_todo("Wrote the spec", None, Some(&yesterday)),
+        make_todo("Fix the build", Some('A'), None),
+        waiting,
+    ]);
+
+    let output = run_command(&["standup"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let done_pos = stdout.find("## Done yesterday").unwrap();
+    let doing_pos = stdout.find("## Doing today").unwrap();
+    let blocked_pos = stdout.find("## Blocked").unwrap();
+    assert!(done_pos < doing_pos && doing_pos < blocked_pos);
+    assert!(stdout.contains("- Wrote the spec"));
+    assert!(stdout.contains("- Fix the build"));
+    assert!(stdout.contains("- Waiting on legal"));
+
+    teardown();
+}
+
+#[test]
+fn test_standup_slack_format() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Fix the build", Some('A'), None)]);
+
+    let output = run_command(&["standup", "--format", "slack"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("*Doing today*"));
+    assert!(stdout.contains("• Fix the build"));
+    assert!(stdout.contains("• Nothing"));
+
+    teardown();
+}
+
+// Webhook notification tests
+
+#[test]
+fn test_notify_dry_run() {
+    setup();
+
+    let mut overdue = make_todo("Pay rent", None, None);
+    overdue.due_date = Some("2020/01/01".to_string());
+    create_test_file_with_todos(vec![overdue, make_todo("Plan trip", None, None)]);
+
+    let output = run_command(&["notify", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("1 overdue"));
+    assert!(stdout.contains("OVERDUE Pay rent"));
+    assert!(!stdout.contains("Plan trip"));
+
+    teardown();
+}
+
+#[test]
+fn test_notify_custom_template_from_config() {
+    setup();
+
+    let mut overdue = make_todo("Pay rent", None, None);
+    overdue.due_date = Some("2020/01/01".to_string());
+    create_test_file_with_todos(vec![overdue]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[notify]\ntemplate = \"Heads up: {overdue_count} late\"\n",
+    )
+    .unwrap();
+
+    let output = run_command(&["notify", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Heads up: 1 late"));
+
+    teardown();
+}
+
+#[test]
+fn test_notify_requires_webhook() {
+    setup();
+
+    let mut overdue = make_todo("Pay rent", None, None);
+    overdue.due_date = Some("2020/01/01".to_string());
+    create_test_file_with_todos(vec![overdue]);
+
+    let output = run_command(&["notify"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(stderr.contains("No webhook URL"));
+
+    teardown();
+}
+
+#[test]
+fn test_notify_nothing_due() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Plan trip", None, None)]);
+
+    let output = run_command(&["notify", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("no notification sent"));
+
+    teardown();
+}
+
+// Change origin metadata, show and log tests
+
+#[test]
+fn test_add_and_done_record_origin_when_enabled() {
+    setup();
+
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[metadata]\nrecord_host = true\nrecord_user = true\n",
+    )
+    .unwrap();
+    run_command_with_input(&["add", "Buy milk"], "Y\n");
+    run_command_with_input(&["done", "1"], "Y\n");
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: serde_json::Value = serde_json::from_str(&content).unwrap();
+    assert!(todos[0]["added_from"]["host"].is_string());
+    assert!(todos[0]["done_from"]["host"].is_string());
+
+    let output = run_command(&["show", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Added from:"));
+    assert!(stdout.contains("Done from:"));
+
+    teardown();
+}
+
+#[test]
+fn test_add_without_metadata_config_records_no_origin() {
+    setup();
+
+    run_command_with_input(&["add", "Buy milk"], "Y\n");
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(content.contains("\"added_from\": null"));
+
+    teardown();
+}
+
+#[test]
+fn test_show_item_details() {
+    setup();
+
+    let mut todo = make_todo("Review code", Some('B'), None);
+    todo.project = Some("Backend".to_string());
+    todo.tags = vec!["urgent".to_string(), "review".to_string()];
+    create_test_file_with_todos(vec![todo]);
+
+    let output = run_command(&["show", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Description: Review code"));
+    assert!(stdout.contains("Priority:    B"));
+    assert!(stdout.contains("Project:     Backend"));
+    assert!(stdout.contains("Tags:        urgent, review"));
+    assert!(!stdout.contains("Added from"));
+
+    let output = run_command(&["show", "5"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("does not exist"));
+
+    teardown();
+}
+
+#[test]
+fn test_log_shows_activity_newest_first() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Buy milk", None, Some("2025/12/01")),
+        make_todo("Send email", None, None),
+    ]);
+
+    let output = run_command(&["log"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let done_pos = stdout.find("2025/12/01 done").unwrap();
+    let added_pos = stdout.find("2025/11/29 added").unwrap();
+    assert!(done_pos < added_pos);
+    assert_eq!(stdout.lines().count(), 3);
+
+    let output = run_command(&["log", "-n", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 1);
+
+    teardown();
+}
+
+#[test]
+fn test_list_modified_since() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Buy milk", None, None),
+        make_todo("Send email", None, None),
+    ]);
+
+    run_command(&["pr", "a", "2"]);
+
+    let output = run_command(&["list", "--modified-since", "1h"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Send email"));
+    assert!(!stdout.contains("Buy milk"));
+
+    let output = run_command(&["list", "--modified-since", "2025-11-29"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Send email"));
+    assert!(stdout.contains("Buy milk"));
+
+    let output = run_command(&["list", "--modified-since", "soon"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid --modified-since value"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert_eq!(content.matches("\"modified\": \"").count(), 1);
+
+    teardown();
+}
+
+// Next item tests
+
+#[test]
+fn test_next_picks_most_urgent() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Low priority", Some('C'), None),
+        make_todo("Top priority", Some('A'), None),
+        make_todo("Finished", Some('A'), Some("2025/11/30")),
+    ]);
+
+    let output = run_command(&["next"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Top priority"));
+    assert_eq!(stdout.lines().count(), 1);
+
+    teardown();
+}
+
+#[test]
+fn test_next_random_skips_done_and_waiting() {
+    setup();
+
+    let mut waiting = make_todo("Waiting", None, None);
+    waiting.context = Some("WF".to_string());
+    create_test_file_with_todos(vec![
+        make_todo("Finished", None, Some("2025/11/30")),
+        waiting,
+        make_todo("Only choice", None, None),
+    ]);
+
+    for _ in 0..5 {
+        let output = run_command(&["next", "--random", "--priority-weighted"]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("Only choice"));
+    }
+
+    teardown();
+}
+
+#[test]
+fn test_next_priority_weighted_requires_random() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Task", None, None)]);
+
+    let output = run_command(&["next", "--priority-weighted"]);
+    assert!(!output.status.success());
+
+    teardown();
+}
+
+// Store lock tests
+
+// A PID far above any real pid_max, so it is never running
+#[cfg(unix)]
+const DEAD_PID: &str = "2147483646";
+
+#[test]
+fn test_commands_release_lock() {
+    setup();
+
+    run_command_with_input(&["add", "Buy milk"], "Y\n");
+    assert!(!test_path(TEST_LOCK_FILE).exists());
+
+    let output = run_command(&["lock", "status"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("is not locked"));
+
+    teardown();
+}
+
+#[test]
+fn test_lock_held_by_running_process_blocks_commands() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+    fs::write(
+        test_path(TEST_LOCK_FILE),
+        format!("{}\n2025/11/29 10:00:00\n", std::process::id()),
+    )
+    .unwrap();
+
+    let output = run_command(&["pr", "a", "1"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("is locked by process"));
+
+    // The item was not modified and the other process's lock is left alone
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(!content.contains("\"A\""));
+    assert!(test_path(TEST_LOCK_FILE).exists());
+
+    let output = run_command(&["lock", "break"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("still running"));
+
+    let output = run_command(&["lock", "break", "--force"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Removed lock"));
+    assert!(!test_path(TEST_LOCK_FILE).exists());
+
+    teardown();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stale_lock_reported_and_broken() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+    fs::write(
+        test_path(TEST_LOCK_FILE),
+        format!("{}\n2025/11/29 10:00:00\n", DEAD_PID),
+    )
+    .unwrap();
+
+    let output = run_command(&["list"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("stale lock"));
+
+    let output = run_command(&["lock", "status"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("not running - stale"));
+
+    run_command(&["lock", "break"]);
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Buy milk"));
+
+    teardown();
+}
+
+// Convert format detection tests
+
+#[test]
+fn test_convert_detects_csv() {
+    setup_convert();
+
+    create_test_txt_file("description,project,due_date\nBuy milk,Home,2026-01-05\n");
+
+    let output = run_command(&["convert", TEST_TXT_FILE, "-o", TEST_OUTPUT_FILE]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    // Named .txt but clearly CSV content: must be chosen explicitly
+    assert!(stderr.contains("Pass --from"));
+    assert!(!stdout.contains("Converted"));
+
+    let output = run_command(&[
+        "convert",
+        TEST_TXT_FILE,
+        "-o",
+        TEST_OUTPUT_FILE,
+        "--from",
+        "csv",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Converted 1 todo items"));
+    assert!(stdout.contains("(csv)"));
+
+    let content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[0].project, Some("Home".to_string()));
+    assert_eq!(todos[0].due_date, Some("2026/01/05".to_string()));
+
+    teardown_convert();
+}
+
+#[test]
+fn test_convert_auto_detects_markdown_without_extension() {
+    setup();
+
+    let input = "test_checklist";
+    fs::write(
+        test_path(input),
+        "## Trip\n- [ ] Pack bags P:Travel\n- [x] Book hotel\n",
+    )
+    .unwrap();
+
+    let output = run_command(&["convert", input, "-o", TEST_OUTPUT_FILE]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Converted 2 todo items"));
+    assert!(stdout.contains("(markdown)"));
+
+    let content = fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[0].project, Some("Travel".to_string()));
+    assert!(todos[1].done_date.is_some());
+
+    teardown();
+}
+
+// Stats and streak tests
+
+fn days_ago(days: i64) -> String {
+    (chrono::Local::now() - chrono::Duration::days(days))
+        .format("%Y/%m/%d")
+        .to_string()
+}
+
+#[test]
+fn test_stats_streak_seeded_from_done_dates() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Two days ago", None, Some(&days_ago(2))),
+        make_todo("Yesterday", None, Some(&days_ago(1))),
+        make_todo("Open", None, None),
+    ]);
+
+    let output = run_command(&["stats", "--streak"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(stdout.contains("Current streak:  2 days"));
+    assert!(stdout.contains("Best streak:     2 days"));
+    assert!(!stdout.contains("Open:"));
+
+    teardown();
+}
+
+#[test]
+fn test_done_records_completion_and_prints_streak() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Yesterday", None, Some(&days_ago(1))),
+        make_todo("Today", None, None),
+    ]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[stats]\nshow_streak_after_done = true\n",
+    )
+    .unwrap();
+
+    let output = run_command_with_input(&["done", "2"], "Y\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Streak: 2 days"));
+
+    let meta: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_META_FILE)).unwrap()).unwrap();
+    assert_eq!(meta["completions"][days_ago(0)], 1);
+    assert_eq!(meta["completions"][days_ago(1)], 1);
+
+    let output = run_command(&["stats"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Open:            0"));
+    assert!(stdout.contains("Completed ever:  2"));
+    assert!(stdout.contains("Completed today: 1"));
+
+    teardown();
+}
+
+// Terminal hyperlink tests
+
+#[test]
+fn test_list_hyperlinks_only_when_forced_or_supported() {
+    setup();
+
+    let mut todo = make_todo("Read https://example.com/docs", None, None);
+    todo.project = Some("Home".to_string());
+    create_test_file_with_todos(vec![todo]);
+
+    // Piped output never contains escape sequences
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Read https://example.com/docs"));
+    assert!(!stdout.contains("\x1b]8;;"));
+
+    let output = Command::new(get_binary_path())
+        .current_dir(test_dir())
+        .args(["list"])
+        .env("FORCE_HYPERLINK", "1")
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\x1b]8;;https://example.com/docs\x1b\\https://example.com/docs"));
+    assert!(stdout.contains("\x1b]8;;todo-cli://project/Home\x1b\\"));
+
+    teardown();
+}
+
+// Delegation and waiting-for tests
+
+#[test]
+fn test_delegate_moves_item_to_waiting_list() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Review budget", None, None),
+        make_todo("Write report", None, None),
+    ]);
+
+    let output = run_command(&["delegate", "1", "--to", "bob", "--follow-up", "2025-12-01"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("delegated to bob, follow up on 2025/12/01"));
+
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Review budget"));
+    assert!(stdout.contains("Write report"));
+
+    let output = run_command(&["waiting"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Review budget Delegated:bob FollowUp:2025/12/01"));
+    assert!(!stdout.contains("Write report"));
+
+    run_command(&["delegate", "1", "--clear"]);
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Review budget"));
+
+    let output = run_command(&["waiting"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Nothing is waiting on anyone"));
+
+    teardown();
+}
+
+#[test]
+fn test_delegate_requires_person_and_valid_date() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Review budget", None, None)]);
+
+    let output = run_command(&["delegate", "1"]);
+    assert!(!output.status.success());
+
+    let output = run_command(&["delegate", "1", "--to", "bob", "--follow-up", "someday"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid follow-up date 'someday'"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(!content.contains("bob"));
+
+    teardown();
+}
+
+// Priority scheme tests
+
+#[test]
+fn test_priority_levels_scheme() {
+    setup();
+
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[priority]\nscheme = \"levels\"\n",
+    )
+    .unwrap();
+    create_test_file_with_todos(vec![
+        make_todo("Buy milk", None, None),
+        make_todo("Send email", None, None),
+    ]);
+
+    run_command(&["pr", "high", "2"]);
+    run_command(&["pr", "low", "1"]);
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(content.contains("\"priority\": \"A\""));
+    assert!(content.contains("\"priority\": \"C\""));
+
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "2 (H) S:2025/11/29 Send email");
+    assert_eq!(lines[1], "1 (L) S:2025/11/29 Buy milk");
+
+    let output = run_command(&["pr", "a", "1"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid priority 'a' (use high, medium, low)"));
+
+    teardown();
+}
+
+#[test]
+fn test_priority_numbers_scheme() {
+    setup();
+
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[priority]\nscheme = \"numbers\"\n",
+    )
+    .unwrap();
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+
+    run_command(&["pr", "1", "1"]);
+
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 (1) S:2025/11/29 Buy milk"));
+
+    let output = run_command(&["show", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Priority:    1"));
+
+    teardown();
+}
+
+// JSON report tests
+
+#[test]
+fn test_reports_as_json() {
+    setup();
+
+    let mut open = make_todo("Write report", Some('A'), None);
+    open.project = Some("Work".to_string());
+    let mut done = make_todo("Buy milk", None, Some("2025/12/01"));
+    done.project = Some("Home".to_string());
+    create_test_file_with_todos(vec![open, done]);
+
+    let output = run_command(&["stats", "--json"]);
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["open"], 1);
+    assert_eq!(stats["completed_total"], 1);
+    assert_eq!(stats["best_streak"], 1);
+
+    let output = run_command(&["projects", "--json"]);
+    let projects: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        projects,
+        serde_json::json!([
+            { "name": "Home", "open": 0, "done": 1 },
+            { "name": "Work", "open": 1, "done": 0 }
+        ])
+    );
+
+    let output = run_command(&["log", "--json", "-n", "1"]);
+    let log: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(log[0]["date"], "2025/12/01");
+    assert_eq!(log[0]["action"], "done");
+    assert_eq!(log[0]["item"]["line_number"], 2);
+
+    let output = run_command(&["standup", "--format", "json"]);
+    let standup: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(standup["doing_today"][0]["description"], "Write report");
+    assert_eq!(standup["doing_today"][0]["priority"], "A");
+
+    teardown();
+}
+
+// Whole-file editing tests
+
+#[cfg(unix)]
+fn run_edit_file(editor: &str, input: &str) -> std::process::Output {
+    use std::io::Write;
+    let mut child = Command::new(get_binary_path())
+        .current_dir(test_dir())
+        .arg("edit-file")
+        .env("EDITOR", editor)
+        .env_remove("VISUAL")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+
+    // A command that fails before reading its input closes the pipe early
+    if let Some(mut stdin) = child.stdin.take()
+        && let Err(e) = stdin.write_all(input.as_bytes())
+        && e.kind() != std::io::ErrorKind::BrokenPipe
+    {
+        panic!("Failed to write to stdin: {}", e);
+    }
+
+    child
+        .wait_with_output()
+        .expect("Failed to wait for command")
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_file_applies_changes() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Buy milk", None, None),
+        make_todo("Send email", Some('B'), None),
+    ]);
+
+    let output = run_edit_file("sed -i s/milk/bread/", "Y\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("- Buy milk S:2025/11/29"));
+    assert!(stdout.contains("+ Buy bread S:2025/11/29"));
+    assert!(stdout.contains("Saved 2 todo items"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(content.contains("Buy bread"));
+    assert!(content.contains("\"priority\": \"B\""));
+
+    let output = run_edit_file("true", "");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("No changes"));
+
+    teardown();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_file_rejects_invalid_lines() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+    let before = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+
+    let output = run_edit_file("sed -i s/S:2025/S:20x5/", "N\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stderr.contains("invalid start date '20x5/11/29'"));
+    assert!(stdout.contains("No changes saved"));
+
+    assert_eq!(
+        fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap(),
+        before
+    );
+
+    teardown();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_file_uses_private_temp_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+    let editor = test_path("editor.sh");
+    fs::write(
+        &editor,
+        "#!/bin/sh\nstat -c %a \"$1\" > file-mode\nstat -c %a \"$(dirname \"$1\")\" > dir-mode\necho \"$1\" > file-path\n",
+    )
+    .unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = run_edit_file(editor.to_str().unwrap(), "");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No changes"));
+
+    let read = |name: &str| {
+        fs::read_to_string(test_path(name))
+            .unwrap()
+            .trim()
+            .to_string()
+    };
+    assert_eq!(read("file-mode"), "600");
+    assert_eq!(read("dir-mode"), "700");
+    // The file and its directory are gone afterwards
+    let path = PathBuf::from(read("file-path"));
+    assert!(!path.parent().unwrap().exists());
+
+    teardown();
+}
+
+// Weekly digest tests
+
+#[test]
+fn test_digest_stdout() {
+    setup();
+
+    let mut overdue = make_todo("Pay rent", None, None);
+    overdue.due_date = Some(days_ago(2));
+    let completed = make_todo("Buy milk", None, Some(&days_ago(1)));
+    create_test_file_with_todos(vec![overdue, completed]);
+
+    let output = run_command(&["digest", "--stdout"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("Todo digest for "));
+    assert!(stdout.contains("Overdue (1)\n- Pay rent"));
+    assert!(stdout.contains("Due this week (0)\n- Nothing"));
+    assert!(stdout.contains("Completed last week (1)\n- Buy milk"));
+
+    let output = run_command(&["digest", "--stdout", "--format", "json"]);
+    let digest: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(digest["overdue"][0]["description"], "Pay rent");
+    assert_eq!(digest["completed_last_week"][0]["line_number"], 2);
+
+    let output = run_command(&["digest", "--stdout", "--format", "html"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("<li>Pay rent"));
+
+    teardown();
+}
+
+#[test]
+fn test_digest_email_requires_recipient() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Pay rent", None, None)]);
+
+    let output = run_command(&["digest"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No recipient"));
+
+    teardown();
+}
+
+#[cfg(not(feature = "email"))]
+#[test]
+fn test_digest_email_without_feature() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Pay rent", None, None)]);
+
+    let output = run_command(&["digest", "--email", "me@example.com"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("built without the 'email' feature"));
+
+    teardown();
+}
+
+// Project and context filter tests
+
+#[test]
+fn test_projects_and_contexts_filters() {
+    setup();
+
+    let with = |description: &str, project: &str, context: &str, done: Option<&str>| {
+        let mut todo = make_todo(description, None, done);
+        todo.project = Some(project.to_string());
+        todo.context = Some(context.to_string());
+        todo
+    };
+    create_test_file_with_todos(vec![
+        with("Fix bug", "Backend", "work", None),
+        with("Write docs", "Docs", "work", None),
+        with("Deploy", "Backend", "work", None),
+        with("Old cleanup", "Docs", "home", Some("2025/11/30")),
+        with("Retro", "Docs", "home", Some("2025/11/30")),
+    ]);
+
+    let output = run_command(&["projects", "--sort", "count"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[1], "  P:Docs (1 open, 2 done)");
+    assert_eq!(lines[2], "  P:Backend (2 open, 0 done)");
+
+    let output = run_command(&["projects", "--open-only", "--min-count", "2"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("P:Backend (2 open)"));
+    assert!(!stdout.contains("P:Docs"));
+
+    let output = run_command(&["contexts"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Contexts:"));
+    assert!(stdout.contains("@home (0 open, 2 done)"));
+    assert!(stdout.contains("@work (3 open, 0 done)"));
+
+    let output = run_command(&["contexts", "--open-only"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("@home"));
+
+    teardown();
+}
+
+// Interactive done tests
+
+#[test]
+fn test_done_interactive_needs_terminal() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+
+    let output = run_command_with_input(&["done", "-i"], "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("done -i needs an interactive terminal"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(content.contains("\"done_date\": null"));
+
+    let output = run_command(&["done"]);
+    assert!(!output.status.success());
+
+    let output = run_command(&["done", "1", "-i"]);
+    assert!(!output.status.success());
+
+    teardown();
+}
+
+// Insert position tests
+
+#[test]
+fn test_add_at_position_renumbers_items() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("First", None, None),
+        make_todo("Second", None, None),
+    ]);
+
+    let output = run_command(&["add", "--top", "Urgent"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Added todo item at position 1"));
+
+    run_command(&["add", "--at", "3", "Middle"]);
+    run_command(&["add", "--at", "5", "Last"]);
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    let descriptions: Vec<&str> = todos.iter().map(|t| t.description.as_str()).collect();
+    assert_eq!(
+        descriptions,
+        vec!["Urgent", "First", "Middle", "Second", "Last"]
+    );
+
+    let output = run_command(&["show", "3"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Description: Middle"));
+
+    teardown();
+}
+
+#[test]
+fn test_add_at_position_out_of_range() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("First", None, None)]);
+
+    let output = run_command(&["add", "--at", "3", "Too far"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Position 3 is out of range (1 to 2)"));
+
+    let output = run_command(&["add", "--at", "0", "Too early"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Position 0 is out of range"));
+
+    let output = run_command(&["add", "--top", "--at", "1", "Both"]);
+    assert!(!output.status.success());
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 1);
+
+    teardown();
+}
+
+// List layout tests
+
+#[test]
+fn test_list_layout_flags() {
+    setup();
+
+    let mut todo = make_todo("Call the plumber", Some('A'), None);
+    todo.context = Some("home".to_string());
+    create_test_file_with_todos(vec![todo]);
+
+    // Piped output has no width, so auto picks the full layout
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 1);
+    assert!(stdout.contains("(A) S:"));
+
+    let output = run_command(&["list", "--layout", "compact"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], "1 (A) Call the plumber");
+    assert!(lines[1].starts_with("      S:"));
+    assert!(lines[1].ends_with("@home"));
+
+    let output = run_command(&["list", "--layout", "full"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
+
+    teardown();
+}
+
+// Attachment tests
+
+#[test]
+fn test_attach_show_and_doctor() {
+    setup();
+
+    let attachment = "test_attachment_spec.txt";
+    fs::write(test_path(attachment), "spec").unwrap();
+    create_test_file_with_todos(vec![make_todo("Review spec", None, None)]);
+
+    let output = run_command(&["attach", "1", attachment]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("as attachment 1"));
+
+    let output = run_command(&["attach", "1", "no_such_file.pdf"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not exist"));
+
+    let output = run_command(&["show", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Attachments:"));
+    assert!(stdout.contains(&format!("1. {}", attachment)));
+
+    let output = run_command(&["open-attachment", "1", "2"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has no attachment 2"));
+
+    let output = run_command(&["doctor"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No problems found"));
+
+    fs::remove_file(test_path(attachment)).unwrap();
+
+    let output = run_command(&["show", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(missing)"));
+
+    let output = run_command(&["doctor"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success());
+    assert!(stdout.contains(&format!(
+        "Item 1: attachment 1 '{}' does not exist",
+        attachment
+    )));
+    assert!(stdout.contains("1 problem found"));
+
+    teardown();
+}
+
+// Clean tests
+
+#[test]
+fn test_clean_dry_run_delete_and_archive() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Ancient", None, Some(&days_ago(200))),
+        make_todo("Open", None, None),
+        make_todo("Recent", None, Some(&days_ago(10))),
+        make_todo("Old", None, Some(&days_ago(120))),
+    ]);
+
+    let output = run_command(&["clean", "--older-than", "90d", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Ancient"));
+    assert!(stdout.contains("Would remove 2 done items"));
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 4);
+
+    let output = run_command(&["clean", "--older-than", "150d", "--archive"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Archived 1 done item to todo-archive.json"));
+    let archived: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_ARCHIVE_FILE)).unwrap()).unwrap();
+    assert_eq!(archived.len(), 1);
+    assert_eq!(archived[0].description, "Ancient");
+
+    let output = run_command(&["clean", "--older-than", "90d"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Removed 1 done item, reclaiming"));
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    let descriptions: Vec<&str> = todos.iter().map(|t| t.description.as_str()).collect();
+    assert_eq!(descriptions, ["Open", "Recent"]);
+
+    // Completions from the removed items still count towards statistics
+    assert!(
+        fs::read_to_string(test_path(TEST_META_FILE))
+            .unwrap()
+            .contains(&days_ago(200))
+    );
+
+    let output = run_command(&["clean", "--older-than", "soon"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid age 'soon'"));
+
+    teardown();
+}
+
+// Porcelain output tests
+
+#[test]
+fn test_porcelain_prints_change_records() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Write report", None, None)]);
+
+    let output = run_command(&["add", "--porcelain", "Buy milk @shop"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let records: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["op"], "add");
+    assert_eq!(records[0]["id"], 2);
+    assert_eq!(records[0]["fields"]["description"], "Buy milk");
+    assert_eq!(records[0]["fields"]["context"], "shop");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Added todo item"));
+
+    let output = run_command(&["--porcelain", "pr", "a", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let record: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(record["op"], "update");
+    assert_eq!(record["id"], 1);
+    assert_eq!(record["fields"]["priority"], "A");
+
+    let output = run_command(&["--porcelain", "add", "--top", "Urgent"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(r#"{"op":"move","from":1,"to":2,"hash":""#));
+    assert!(stdout.contains(r#"{"op":"move","from":2,"to":3,"hash":""#));
+    assert!(stdout.contains(r#""op":"add","id":1"#));
+
+    // Failed commands change nothing, so there is nothing to report
+    let output = run_command(&["--porcelain", "pr", "a", "9"]);
+    assert!(output.stdout.is_empty());
+
+    // Read-only commands are unaffected
+    let output = run_command(&["--porcelain", "list"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Urgent"));
+
+    teardown();
+}
+
+// Taskwarrior export tests
+
+#[test]
+fn test_export_taskwarrior_and_convert_back() {
+    setup();
+
+    let export_file = "test_taskwarrior.json";
+    let mut todo = make_todo("Ship release", Some('A'), None);
+    todo.project = Some("Launch".to_string());
+    todo.context = Some("work".to_string());
+    create_test_file_with_todos(vec![todo, make_todo("Old task", None, Some("2025/11/30"))]);
+
+    let output = run_command(&["export", "--format", "taskwarrior"]);
+    let tasks: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0]["description"], "Ship release");
+    assert_eq!(tasks[0]["status"], "pending");
+    assert_eq!(tasks[0]["priority"], "H");
+    assert_eq!(tasks[0]["project"], "Launch");
+    assert_eq!(tasks[0]["tags"][0], "work");
+    assert_eq!(tasks[1]["status"], "completed");
+    assert!(tasks[1]["end"].as_str().unwrap().ends_with('Z'));
+
+    let output = run_command(&["export", "--format", "taskwarrior", "-o", export_file]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Exported 2 todo items"));
+
+    let output = run_command(&["convert", export_file, "-o", TEST_OUTPUT_FILE]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(taskwarrior)"));
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_OUTPUT_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 2);
+    assert_eq!(todos[0].priority, Some('A'));
+    assert_eq!(todos[1].done_date.as_deref(), Some("2025/11/30"));
+
+    teardown();
+}
+
+// Google Tasks tests
+
+#[test]
+fn test_export_gtasks_and_import_back() {
+    setup();
+
+    let export_file = "Tasks.json";
+    let mut todo = make_todo("Ship release", Some('A'), None);
+    todo.project = Some("Launch".to_string());
+    todo.context = Some("work".to_string());
+    todo.due_date = Some("2025/12/05".to_string());
+    create_test_file_with_todos(vec![todo, make_todo("Old task", None, Some("2025/11/30"))]);
+
+    let output = run_command(&["export", "--format", "gtasks"]);
+    let lists: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(lists["kind"], "tasks#taskLists");
+    assert_eq!(lists["items"][0]["title"], "My Tasks");
+    assert_eq!(lists["items"][0]["items"][0]["title"], "Old task");
+    assert_eq!(lists["items"][0]["items"][0]["status"], "completed");
+    assert_eq!(lists["items"][1]["title"], "Launch");
+    assert_eq!(
+        lists["items"][1]["items"][0]["title"],
+        "(A) Ship release @work"
+    );
+    assert_eq!(
+        lists["items"][1]["items"][0]["due"],
+        "2025-12-05T00:00:00.000Z"
+    );
+
+    let output = run_command(&["export", "--format", "gtasks", "-o", export_file]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Exported 2 todo items"));
+
+    create_test_file_with_todos(vec![]);
+    let output = run_command(&["import", export_file]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(gtasks)"));
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 2);
+    assert_eq!(todos[0].description, "Old task");
+    assert_eq!(todos[0].project, None);
+    assert_eq!(todos[0].done_date.as_deref(), Some("2025/11/30"));
+    assert_eq!(todos[1].description, "Ship release");
+    assert_eq!(todos[1].priority, Some('A'));
+    assert_eq!(todos[1].context.as_deref(), Some("work"));
+    assert_eq!(todos[1].project.as_deref(), Some("Launch"));
+    assert_eq!(todos[1].due_date.as_deref(), Some("2025/12/05"));
+
+    teardown();
+}
+
+// Template tests
+
+#[test]
+fn test_add_from_template() {
+    setup();
+
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[templates]\n\
+         weekly-report = \"Weekly report {{week}} for {{team}} P:{{project}} @work\"\n\
+         \n\
+         [variables]\n\
+         team = \"Platform\"\n\
+         project = \"Ops\"\n",
+    )
+    .unwrap();
+    create_test_file_with_todos(vec![]);
+
+    let output = run_command(&[
+        "add",
+        "--template",
+        "weekly-report",
+        "--var",
+        "project=Launch",
+        "T:review",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Added todo item"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 1);
+    assert!(todos[0].description.starts_with("Weekly report 20"));
+    assert!(todos[0].description.ends_with("for Platform"));
+    assert_eq!(todos[0].project.as_deref(), Some("Launch"));
+    assert_eq!(todos[0].context.as_deref(), Some("work"));
+    assert_eq!(todos[0].tags, ["review"]);
+
+    let output = run_command(&["add", "--template", "missing"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No template named 'missing'"));
+    assert!(stderr.contains("Available templates: weekly-report"));
+
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[templates]\nstandup = \"Standup notes for {{team}}\"\n",
+    )
+    .unwrap();
+    let output = run_command(&["add", "--template", "standup"]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("No value for placeholder {{team}} in template 'standup'")
+    );
+
+    teardown();
+}
+
+// Calendar tests
+
+#[test]
+fn test_calendar_month_view() {
+    setup();
+
+    let mut ship = make_todo("Ship release", Some('A'), None);
+    ship.due_date = Some("2025/12/10".to_string());
+    let mut review = make_todo("Review budget", None, None);
+    review.due_date = Some("2025/12/10".to_string());
+    let mut later = make_todo("Plan next year", None, None);
+    later.due_date = Some("2026/01/05".to_string());
+    create_test_file_with_todos(vec![ship, review, later]);
+
+    let output = run_command(&["calendar", "--month", "2025-12"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("December 2025"));
+    assert!(stdout.contains(" 10(2)"));
+    assert!(stdout.contains("2025/12/10 Due       1 (A) Ship release"));
+    assert!(stdout.contains("2025/12/10 Due       2 Review budget"));
+    assert!(!stdout.contains("Plan next year"));
+
+    let output = run_command(&["calendar", "--month", "2025-11"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Nothing due this month"));
+
+    let output = run_command(&["calendar", "--month", "Dec"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid month 'Dec'"));
+
+    teardown();
+}
+
+// Batch mode tests
+
+#[test]
+fn test_batch_applies_commands_and_saves_once() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Existing", None, None)]);
+
+    let output = run_command_with_input(
+        &["batch"],
+        "# Monday planning\nadd \"Write report @work\"\nadd --top 'Call bank'\npr a 3\ndone 2\nlist\n",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Ran 5 commands"));
+    // Later commands see the changes made by earlier ones
+    assert!(stdout.contains("1 S:"));
+    assert!(stdout.contains("(A)"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    let descriptions: Vec<&str> = todos.iter().map(|t| t.description.as_str()).collect();
+    assert_eq!(descriptions, ["Call bank", "Existing", "Write report"]);
+    assert!(todos[1].done_date.is_some());
+    assert_eq!(todos[2].priority, Some('A'));
+
+    // A bad line stops the whole batch before anything runs
+    let output = run_command_with_input(&["batch"], "add \"Never added\"\nedit-file\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("line 2: 'edit-file' can't be used in a batch"));
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(!content.contains("Never added"));
+
+    teardown();
+}
+
+#[test]
+fn test_batch_saves_nothing_when_a_command_fails() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Old chore", None, Some("2020/01/01")),
+        make_todo("Water plants", None, None),
+    ]);
+    fs::write(test_path(TEST_META_FILE), r#"{"completions": {}}"#).unwrap();
+
+    // The archive and completion counts are held back with the list, so a failing
+    // command leaves all three as they were
+    let output = run_command_with_input(
+        &["batch"],
+        "clean --older-than 1d --archive\ndone 1\nexport --format taskwarrior -o missing/x.json\n",
+    );
+    assert!(!output.status.success());
+    assert!(!test_path(TEST_ARCHIVE_FILE).exists());
+    assert_eq!(
+        fs::read_to_string(test_path(TEST_META_FILE)).unwrap(),
+        r#"{"completions": {}}"#
+    );
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 2);
+    assert!(todos[1].done_date.is_none());
+
+    // Once every command succeeds, all three are saved
+    let output = run_command_with_input(&["batch"], "clean --older-than 1d --archive\ndone 1\n");
+    assert!(output.status.success());
+    let archived = fs::read_to_string(test_path(TEST_ARCHIVE_FILE)).unwrap();
+    assert!(archived.contains("Old chore"));
+    let meta: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_META_FILE)).unwrap()).unwrap();
+    assert_eq!(meta["completions"][days_ago(0)], 1);
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 1);
+    assert!(todos[0].done_date.is_some());
+
+    teardown();
+}
+
+// Done confirmation policy tests
+
+#[test]
+fn test_done_confirmation_policy() {
+    setup();
+
+    let mut urgent = make_todo("Ship release", Some('A'), None);
+    urgent.project = Some("Launch".to_string());
+    create_test_file_with_todos(vec![urgent, make_todo("Water plants", Some('C'), None)]);
+
+    // The default asks, and says how old the item is and which project it's in
+    let output = run_command_with_input(&["done", "1"], "N\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Mark this item as done?"));
+    assert!(stdout.contains(", project Launch"));
+    assert!(stdout.contains("Cancelled"));
+
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[done]\nconfirm = \"high-priority\"\n",
+    )
+    .unwrap();
+
+    let output = run_command(&["done", "2"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Mark this item as done?"));
+    assert!(stdout.contains("Todo item 2 marked as done"));
+
+    let output = run_command_with_input(&["done", "1"], "N\n");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Mark this item as done?"));
+
+    fs::write(test_path(TEST_CONFIG_FILE), "[done]\nconfirm = \"never\"\n").unwrap();
+
+    let output = run_command(&["done", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Todo item 1 marked as done"));
+
+    teardown();
+}
+
+// List grouping tests
+
+#[test]
+fn test_list_group_done_last() {
+    setup();
+
+    let mut done = make_todo("Finished task", Some('A'), Some(&days_ago(1)));
+    done.due_date = Some(days_ago(3));
+    let mut overdue = make_todo("Late task", None, None);
+    overdue.due_date = Some(days_ago(2));
+    create_test_file_with_todos(vec![
+        done,
+        make_todo("Important task", Some('A'), None),
+        overdue,
+    ]);
+
+    let output = run_command(&["list", "--all", "--pr", "--group", "done-last"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].contains("Late task"));
+    assert!(lines[1].contains("Important task"));
+    assert!(lines[2].contains("Finished task"));
+
+    // Without grouping, the done item's priority and due date put it first
+    let output = run_command(&["list", "--all"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().next().unwrap().contains("Finished task"));
+
+    teardown();
+}
+
+// Project and context color tests
+
+#[test]
+fn test_project_color_override() {
+    setup();
+
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[colors.projects]\nWork = \"red\"\n",
+    )
+    .unwrap();
+    let mut todo = make_todo("Send invoice", None, None);
+    todo.project = Some("Work".to_string());
+    create_test_file_with_todos(vec![todo]);
+
+    let output = Command::new(get_binary_path())
+        .current_dir(test_dir())
+        .args(["list"])
+        .env("CLICOLOR_FORCE", "1")
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("P:\x1b[31mWork\x1b[0m"));
+
+    let output = Command::new(get_binary_path())
+        .current_dir(test_dir())
+        .args(["projects"])
+        .env("CLICOLOR_FORCE", "1")
+        .output()
+        .expect("Failed to execute command");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("P:\x1b[31mWork\x1b[0m"));
+
+    teardown();
+}
+
+// Import tests
+
+#[test]
+fn test_import_csv_with_column_map() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Existing task", None, None)]);
+    create_test_txt_file("Title,Deadline\nPay rent,31/01/2025\nCall bank,03/02/2025\n");
+
+    let output = run_command(&[
+        "import",
+        TEST_TXT_FILE,
+        "--map",
+        "description=Title,due=Deadline",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Imported 2 todo items"));
+    assert!(stdout.contains("(csv)"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 3);
+    assert_eq!(todos[0].description, "Existing task");
+    assert_eq!(todos[1].due_date, Some("2025/01/31".to_string()));
+    // Day-first, since 31/01 rules out month-first for the whole column
+    assert_eq!(todos[2].due_date, Some("2025/02/03".to_string()));
+
+    // A mapping only makes sense for CSV input
+    let output = run_command(&[
+        "import",
+        TEST_TXT_FILE,
+        "--format",
+        "txt",
+        "--map",
+        "description=Title",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--map only applies to CSV"));
+
+    teardown();
+}
+
+// Init and completions tests
+
+#[test]
+fn test_completions_prints_script() {
+    setup();
+
+    let output = run_command(&["completions", "fish"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("complete -c todo-cli"));
+    // No list is needed, so none is created
+    assert!(!test_path(TEST_TODO_FILE).exists());
+
+    teardown();
+}
+
+#[test]
+fn test_init_needs_a_terminal() {
+    setup();
+
+    let output = run_command_with_input(&["init"], "\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("init needs an interactive terminal"));
+    assert!(!test_path(TEST_TODO_FILE).exists());
+
+    teardown();
+}
+
+// Triage tests
+
+#[test]
+fn test_triage_classifies_inbox_items() {
+    setup();
+
+    let mut old = make_todo("Call the bank", None, None);
+    old.start_date = days_ago(30);
+    let mut filed = make_todo("Filed already", None, None);
+    filed.project = Some("Work".to_string());
+    let mut recent = make_todo("Renew passport", None, None);
+    recent.start_date = days_ago(2);
+    let mut skipped = make_todo("Someday maybe", None, None);
+    skipped.start_date = days_ago(1);
+    create_test_file_with_todos(vec![recent, filed, old, skipped]);
+
+    // Oldest first: the bank call, then the passport, then the skipped item
+    let output = run_command_with_input(&["triage"], "soon\nA @phone P:Admin\ndone\n\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains("3 items to triage"));
+    assert!(stdout.contains("[1/3] 3 Call the bank"));
+    assert!(stderr.contains("'soon' is not a priority"));
+    assert!(stdout.contains("Triaged 2 of 3 items; 1 left in the inbox"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[2].priority, Some('A'));
+    assert_eq!(todos[2].context, Some("phone".to_string()));
+    assert_eq!(todos[2].project, Some("Admin".to_string()));
+    assert!(todos[0].done_date.is_some());
+    assert!(todos[3].done_date.is_none());
+    assert_eq!(todos[3].project, None);
+
+    teardown();
+}
+
+// Logging tests
+
+#[test]
+fn test_verbose_logging() {
+    setup();
+    let log_file = "test_todo.log";
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+
+    // Quiet by default
+    let output = run_command(&["list"]);
+    assert!(String::from_utf8_lossy(&output.stderr).is_empty());
+
+    let output = run_command(&["list", "-v"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("DEBUG read list"));
+    assert!(stderr.contains("items=1"));
+    assert!(!stderr.contains("TRACE"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Buy milk"));
+
+    let output = run_command(&["-vv", "list", "--log-file", log_file]);
+    assert!(String::from_utf8_lossy(&output.stderr).is_empty());
+    let log = fs::read_to_string(test_path(log_file)).unwrap();
+    assert!(log.contains("filter: open and not delegated"));
+    assert!(log.contains("TRACE"));
+    assert!(!log.contains('\x1b'));
+
+    teardown();
+}
+
+// Piped output tests
+
+#[test]
+fn test_list_piped_without_file_does_not_prompt() {
+    setup();
+
+    // Even with a "Y" waiting on stdin, nothing is asked and nothing is created
+    let output = run_command_with_input(&["list"], "Y\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert_eq!(stdout, "No todo items found\n");
+    assert!(!test_path(TEST_TODO_FILE).exists());
+
+    teardown();
+}
+
+#[test]
+fn test_list_piped_output_is_plain() {
+    setup();
+
+    let mut todo = make_todo("Send invoice", Some('A'), None);
+    todo.project = Some("Work".to_string());
+    todo.start_date = "2025/01/02".to_string();
+    todo.due_date = Some("2025/01/09".to_string());
+    create_test_file_with_todos(vec![todo]);
+
+    // A narrow COLUMNS must not switch piped output to the compact layout
+    let output = Command::new(get_binary_path())
+        .current_dir(test_dir())
+        .args(["list"])
+        .env("COLUMNS", "20")
+        .env_remove("CLICOLOR_FORCE")
+        .output()
+        .expect("Failed to execute command");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "1 (A) S:2025/01/02 Due:2025/01/09 Send invoice P:Work\n"
+    );
+
+    teardown();
+}
+
+// Schedule and agenda tests
+
+#[test]
+fn test_schedule_and_agenda() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Write report", None, None),
+        make_todo("Call bank", Some('A'), None),
+    ]);
+
+    let output = run_command(&["schedule", "2", "today"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("Todo item 2 scheduled for {}", days_ago(0))));
+
+    let output = run_command(&["schedule", "1", "+2d"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("scheduled for"));
+
+    let output = run_command(&["schedule", "1", "someday"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid day 'someday'"));
+
+    let output = run_command(&["agenda"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("(today)"));
+    assert!(stdout.contains("2 (A) Call bank"));
+    assert!(!stdout.contains("Write report"));
+
+    let output = run_command(&["agenda", "--week"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    let report = lines
+        .iter()
+        .position(|l| l.contains("Write report"))
+        .unwrap();
+    // Listed under its day, two days from now
+    assert!(lines[report - 1].contains(&days_ago(-2)));
+    assert_eq!(stdout.matches("Nothing planned").count(), 5);
+
+    let output = run_command(&["list"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("Sched:{}", days_ago(0))));
+
+    let output = run_command(&["schedule", "2", "--clear"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no longer scheduled"));
+
+    teardown();
+}
+
+// Remembered list view tests
+
+#[test]
+fn test_piped_list_ignores_remembered_view() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Open task", None, None),
+        make_todo("Finished task", None, Some("2025/01/01")),
+    ]);
+    fs::write(
+        test_path(TEST_STATE_FILE),
+        r#"{"list_view": {"all": true, "group": "done-last"}}"#,
+    )
+    .unwrap();
+
+    // Scripts get exactly what they ask for, and don't change the remembered view
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Open task"));
+    assert!(!stdout.contains("Finished task"));
+
+    run_command(&["list", "--hide-waiting"]);
+    assert!(
+        fs::read_to_string(test_path(TEST_STATE_FILE))
+            .unwrap()
+            .contains("done-last")
+    );
+
+    // --reset-view forgets it
+    run_command(&["list", "--reset-view"]);
+    let state = fs::read_to_string(test_path(TEST_STATE_FILE)).unwrap();
+    assert!(state.contains("\"list_view\": null"));
+
+    teardown();
+}
+
+// Bulk tag tests
+
+#[test]
+fn test_tag_add_remove_and_clear() {
+    setup();
+
+    let mut legacy = make_todo("Port the old report", None, None);
+    legacy.project = Some("Legacy".to_string());
+    legacy.tags = vec!["old".to_string()];
+    let mut other = make_todo("Write the new report", None, None);
+    other.tags = vec!["old".to_string(), "draft".to_string()];
+    create_test_file_with_todos(vec![legacy, other, make_todo("Book a room", None, None)]);
+
+    let output = run_command(&["tag", "add", "sprint", "1", "3"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Added tag 'sprint' to 2 items (1, 3)"));
+
+    // Items that already have the tag are left alone
+    let output = run_command(&["tag", "add", "T:sprint", "3"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No items changed"));
+
+    let output = run_command(&["tag", "remove", "old", "--all-matching", "project:Legacy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Removed tag 'old' from 1 item (1)"));
+
+    let output = run_command(&["tag", "clear", "2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Cleared tags from 1 item (2)"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].tags, vec!["sprint"]);
+    assert!(todos[1].tags.is_empty());
+    assert_eq!(todos[2].tags, vec!["sprint"]);
+
+    teardown();
+}
+
+#[test]
+fn test_tag_rejects_bad_targets() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+    let before = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+
+    // One bad line number means nothing is changed
+    let output = run_command(&["tag", "add", "sprint", "1", "5"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Todo item 5 does not exist"));
+
+    let output = run_command(&["tag", "add", "sprint", "--all-matching", "owner:me"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown match field 'owner'"));
+
+    assert_eq!(
+        fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap(),
+        before
+    );
+
+    teardown();
+}
+
+// List summary tests
+
+#[test]
+fn test_list_count_only() {
+    setup();
+
+    let mut overdue = make_todo("Pay rent", None, None);
+    overdue.due_date = Some(days_ago(3));
+    create_test_file_with_todos(vec![
+        overdue,
+        make_todo("Buy milk", None, None),
+        make_todo("Old task", None, Some("2025/01/01")),
+    ]);
+
+    let output = run_command(&["list", "--count-only"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2 open, 0 done shown, 1 overdue\n"
+    );
+
+    let output = run_command(&["list", "--all", "--count-only"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2 open, 1 done shown, 1 overdue\n"
+    );
+
+    // The footer is for terminals; piped output stays one item per line
+    fs::write(test_path(TEST_CONFIG_FILE), "[list]\nsummary = true\n").unwrap();
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(!stdout.contains("overdue"));
+
+    teardown();
+}
+
+// Sync tests
+
+#[test]
+fn test_sync_needs_a_url() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+
+    let output = run_command(&["sync"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No sync URL"));
+
+    let output = run_command(&["sync", "https://example.com/todo"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid sync URL"));
+
+    teardown();
+}
+
+// Saved report tests
+
+#[test]
+fn test_report_runs_saved_report() {
+    setup();
+
+    let mut work = make_todo("Fix bug", Some('A'), None);
+    work.project = Some("Work".to_string());
+    create_test_file_with_todos(vec![work, make_todo("Buy milk", None, None)]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[reports.mine]\ndescription = \"My work\"\nfilter = \"project:Work\"\n\
+         columns = [\"id\", \"description\"]\n",
+    )
+    .unwrap();
+
+    let output = run_command(&["report", "--list"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("mine  My work"));
+
+    let output = run_command(&["report", "mine"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "ID  Description\n1   Fix bug\n"
+    );
+
+    let output = run_command(&["report", "theirs"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No report named 'theirs'"));
+
+    teardown();
+}
+
+// List file tests
+
+#[test]
+fn test_file_flag_uses_another_list() {
+    setup();
+
+    fs::create_dir_all(test_path("lists")).unwrap();
+    let work = test_path("lists").join("work.json");
+    fs::write(&work, "[]").unwrap();
+    fs::write(
+        test_path("lists").join(TEST_CONFIG_FILE),
+        "[done]\nconfirm = \"never\"\n",
+    )
+    .unwrap();
+
+    let output = run_command(&["add", "--file", "lists/work.json", "Review budget"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Added todo item"));
+    assert!(fs::read_to_string(&work).unwrap().contains("Review budget"));
+    assert!(!test_path(TEST_TODO_FILE).exists());
+
+    // The list's own config applies, and its companion files are named after it
+    let output = run_command(&["-f", "lists/work.json", "done", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("marked as done"));
+    assert!(test_path("lists").join("work.meta.json").exists());
+    assert!(!test_path("lists").join("work.json.lock").exists());
+
+    // A directory means the todo.json in it
+    fs::write(test_path("lists").join(TEST_TODO_FILE), "[]").unwrap();
+    run_command(&["-f", "lists", "add", "Call the bank"]);
+    let output = run_command(&["list", "-f", "lists"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Call the bank"));
+    assert!(!stdout.contains("Review budget"));
+
+    teardown();
+}
+
+// Rollover tests
+
+#[test]
+fn test_rollover_moves_missed_items_to_today() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Write report", None, None),
+        make_todo("Plan trip", None, None),
+    ]);
+    run_command(&["schedule", "1", "2020-01-06"]);
+    run_command(&["schedule", "2", "+3d"]);
+
+    let output = run_command(&["rollover"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Rolled over 1 item to"));
+    assert!(stdout.contains("(1)"));
+
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Rolled:1"));
+    assert!(!stdout.contains("Sched:2020/01/06"));
+
+    let output = run_command(&["rollover"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No missed items to roll over"));
+
+    teardown();
+}
+
+#[test]
+fn test_automatic_rollover() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Write report", None, None)]);
+    run_command(&["schedule", "1", "2020-01-06"]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[rollover]\nautomatic = true\n",
+    )
+    .unwrap();
+
+    // The note goes to stderr, leaving the list output as it was
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Rolled over 1 missed item to today (1)"));
+    assert!(!stdout.contains("Rolled over"));
+    assert!(stdout.contains("Rolled:1"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(content.contains("\"rollovers\": 1"));
+
+    teardown();
+}
+
+// Quiet mode tests
+
+#[test]
+fn test_quiet_drops_confirmations_only() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+    fs::write(test_path(TEST_CONFIG_FILE), "[done]\nconfirm = \"never\"\n").unwrap();
+
+    let output = run_command(&["--quiet", "add", "Buy milk"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    let output = run_command(&["done", "1", "-q"]);
+    assert!(output.stdout.is_empty());
+
+    // Requested output and errors are still printed
+    let output = run_command(&["-q", "list", "--all"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Buy milk"));
+
+    let output = run_command(&["-q", "done", "5"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Todo item 5 does not exist"));
+
+    // With --porcelain only the change records are left
+    let output = run_command(&["-q", "--porcelain", "add", "Eggs"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("{\"op\":\"add\""));
+    assert!(output.stderr.is_empty());
+
+    teardown();
+}
+
+// Next with a time limit tests
+
+#[test]
+fn test_next_time_fits_estimates() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+    run_command(&["add", "(A) Write report est:45m"]);
+    run_command(&["add", "Call the bank est:10m"]);
+    run_command(&["add", "Review PR est:20m"]);
+    run_command(&["add", "Tidy desk"]);
+
+    let output = run_command(&["next", "--time", "30m"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Write report"));
+    assert!(stdout.contains("Call the bank"));
+    assert!(stdout.contains("Review PR"));
+    assert!(stdout.contains("30m of 30m planned"));
+    assert!(stdout.contains("1 open item has no estimate"));
+
+    let output = run_command(&["next", "--time", "1h"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Write report"));
+
+    let output = run_command(&["next", "--time", "5m"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Nothing fits in 5m"));
+
+    let output = run_command(&["next", "--time", "soon"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid time 'soon'"));
+
+    teardown();
+}
+
+// Bump tests
+
+#[test]
+fn test_bump_due_dates() {
+    setup();
+
+    let mut late = make_todo("Write report", None, None);
+    late.due_date = Some("2020/01/01".to_string());
+    let mut later = make_todo("Pay rent", None, None);
+    later.due_date = Some("2099/01/01".to_string());
+    create_test_file_with_todos(vec![late, later, make_todo("Tidy desk", None, None)]);
+
+    let output = run_command(&["bump", "2", "+2d"]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Todo item 2 now due 2099/01/03 (was 2099/01/01)")
+    );
+
+    let output = run_command(&["bump", "3", "+2d"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has no due date"));
+
+    let output = run_command(&["bump", "1", "2d"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid amount '2d'"));
+
+    for args in [
+        &["bump", "1", "+99999999d"][..],
+        &["bump", "1", "+99999999999999999y"],
+        &["bump", "--overdue", "+999999999999d"],
+    ] {
+        let output = run_command(args);
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid date shift"));
+    }
+
+    let output = run_command(&["bump", "--overdue", "+1w"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 Write report: 2020/01/01 -> 2020/01/08"));
+    assert!(stdout.contains("Bumped 1 overdue item by +1w"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].due_date.as_deref(), Some("2020/01/08"));
+    assert_eq!(todos[1].due_date.as_deref(), Some("2099/01/03"));
+    assert_eq!(todos[2].due_date, None);
+
+    teardown();
+}
+
+// Project archive tests
+
+#[test]
+fn test_project_archive_hides_items() {
+    setup();
+
+    let mut old = make_todo("Send final invoice", None, None);
+    old.project = Some("Old-Client".to_string());
+    create_test_file_with_todos(vec![old, make_todo("Buy milk", None, None)]);
+
+    let output = run_command(&["project", "archive", "Old-Client"]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Archived project 'Old-Client'; 1 item hidden")
+    );
+
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Send final invoice"));
+    assert!(stdout.contains("Buy milk"));
+
+    let output = run_command(&["list", "--include-archived-projects"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Send final invoice"));
+
+    let output = run_command(&["projects"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(1 open, 0 done, archived)"));
+
+    let meta = fs::read_to_string(test_path(TEST_META_FILE)).unwrap();
+    assert!(meta.contains("Old-Client"));
+
+    let output = run_command(&["project", "archive", "Nobody"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No items in project 'Nobody'"));
+
+    run_command(&["project", "unarchive", "Old-Client"]);
+    let output = run_command(&["list"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Send final invoice"));
+
+    teardown();
+}
+
+// Replace tests
+
+#[test]
+fn test_replace_in_descriptions() {
+    setup();
+
+    let mut work = make_todo("Call clinet", None, None);
+    work.project = Some("Work".to_string());
+    create_test_file_with_todos(vec![work, make_todo("Email clinet", None, None)]);
+
+    let output = run_command(&["replace", "clinet", "client", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("+ Call client"));
+    assert!(stdout.contains("Would change 2 items (1, 2)"));
+
+    let output = run_command_with_input(&["replace", "clinet", "client"], "n\n");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Cancelled"));
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(!content.contains("client"));
+
+    let output =
+        run_command_with_input(&["replace", "clinet", "client", "--project", "Work"], "y\n");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Changed 1 item (1)"));
+
+    let output = run_command(&[
+        "replace",
+        "--regex",
+        "(\\w+) clinet",
+        "$1 the client",
+        "--yes",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Changed 1 item (2)"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].description, "Call client");
+    assert_eq!(todos[1].description, "Email the client");
+
+    let output = run_command(&["replace", "--regex", "(", "x"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid regular expression"));
+
+    teardown();
+}
+
+// Implication rule tests
+
+#[test]
+fn test_rules_infer_metadata_on_add_and_edit() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Call the bank", None, None)]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[rules]\nimplies = [\"tag:bug implies project:Engineering\", \"context:phone implies tag:quick\"]\n",
+    )
+    .unwrap();
+
+    let output = run_command(&["add", "Fix login crash T:bug"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Inferred project:Engineering"));
+    assert!(stdout.contains("Added todo item"));
+
+    // Keep everything but set the context to phone
+    let output = run_command_with_input(&["edit", "1"], "\n\nphone\n\n\n\n");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Inferred tag:quick"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].tags, ["quick"]);
+    assert_eq!(todos[1].project.as_deref(), Some("Engineering"));
+
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[rules]\nimplies = [\"tag:bug means project:Engineering\"]\n",
+    )
+    .unwrap();
+    let output = run_command(&["add", "Another bug T:bug"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid rule"));
+
+    teardown();
+}
+
+// Oneline ids and --stdin tests
+
+#[test]
+fn test_oneline_ids_pipe_into_done_and_pr() {
+    setup();
+
+    let mut waiting = make_todo("Quote from the builder", None, None);
+    waiting.context = Some("WF".to_string());
+    create_test_file_with_todos(vec![
+        make_todo("Write report", None, None),
+        waiting,
+        make_todo("Review plan", Some('A'), None),
+    ]);
+
+    let output = run_command(&["list", "--hide-waiting", "--oneline-ids"]);
+    let ids = String::from_utf8_lossy(&output.stdout).to_string();
+    assert_eq!(ids, "3\tReview plan\n1\tWrite report\n");
+
+    let output = run_command_with_input(&["pr", "B", "--stdin"], &ids);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Set priority for todo item 3"));
+
+    let output = run_command_with_input(&["done", "--stdin"], &ids);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Todo item 3 marked as done"));
+    assert!(stdout.contains("Todo item 1 marked as done"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].priority, Some('B'));
+    assert_eq!(todos[2].priority, Some('B'));
+    assert!(todos[0].done_date.is_some());
+    assert!(todos[1].done_date.is_none());
+    assert!(todos[2].done_date.is_some());
+
+    // Nothing is marked when any line number is bad
+    let output = run_command_with_input(&["done", "--stdin"], "2\n9\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Todo item 9 does not exist"));
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert!(todos[1].done_date.is_none());
+
+    // An empty match prints nothing at all
+    let output = run_command(&["list", "--hide-waiting", "--oneline-ids"]);
+    assert!(output.stdout.is_empty());
+
+    teardown();
+}
+
+// Checksum tests
+
+#[test]
+fn test_checksum_warns_about_outside_changes() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Call the bank", None, None)]);
+    run_command(&["add", "Write report"]);
+    assert!(test_path("todo.sum.json").exists());
+
+    let output = run_command(&["list"]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Warning"));
+
+    // Lose the second item, as a partial write or a bad hand edit might
+    create_test_file_with_todos(vec![make_todo("Call the bank", None, None)]);
+    let output = run_command(&["list"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Warning:").count(), 1);
+    assert!(stderr.contains("has 1 items, but was saved with 2"));
+
+    let output = run_command(&["doctor"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("doctor --accept"));
+
+    let output = run_command(&["doctor", "--accept"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Accepted"));
+    let output = run_command(&["list"]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Warning"));
+
+    teardown();
+}
+
+// Priority limit tests
+
+#[test]
+fn test_priority_limit_needs_force() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Ship release", Some('A'), None),
+        make_todo("Fix crash", None, None),
+        make_todo("Tidy docs", None, None),
+    ]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[priority]\nlimits = { A = 1 }\n",
+    )
+    .unwrap();
+
+    let output = run_command(&["pr", "A", "2"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Priority A would have 2 open items, over its limit of 1"));
+    assert!(stderr.contains("--force"));
+
+    let output = run_command(&["doctor"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = run_command(&["pr", "A", "2", "--force"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Set priority for todo item 2"));
+
+    let output = run_command(&["doctor"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Priority A has 2 open items, over its limit of 1")
+    );
+
+    // Moving items that are already over the limit is still allowed
+    let output = run_command(&["pr", "A", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Set priority for todo item 1"));
+
+    // add keeps to the limits too, whether the priority is given or a default
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[priority]\nlimits = { A = 2, C = 0 }\ndefault_priority = \"C\"\n",
+    )
+    .unwrap();
+    for args in [
+        &["add", "--pri", "A", "Another fire"][..],
+        &["add", "(A) Another fire"],
+        &["add", "Another chore"],
+    ] {
+        let output = run_command(args);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("over its limit"),
+            "{:?}",
+            args
+        );
+    }
+    let output = run_command(&["add", "--pri", "B", "Another chore"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Added todo item"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 4);
+
+    teardown();
+}
+
+// Command history tests
+
+#[test]
+fn test_history_and_again() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Call the bank", None, None)]);
+    run_command(&["add", "Water the plants @home"]);
+    run_command(&["list", "--all"]);
+    run_command(&["done", "1"]);
+
+    let output = run_command(&["history"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("todo-cli add 'Water the plants @home'"));
+    assert!(stdout.contains("todo-cli list --all"));
+    assert!(stdout.contains("todo-cli done 1"));
+
+    // Recording a command that only looks at the list doesn't touch the state file
+    assert!(!test_path(TEST_STATE_FILE).exists());
+    assert_eq!(
+        fs::read_to_string(test_path(TEST_HISTORY_FILE))
+            .unwrap()
+            .lines()
+            .count(),
+        3
+    );
+
+    let output = run_command(&["again", "add"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Running: todo-cli add 'Water the plants @home'"));
+    assert!(stdout.contains("Added todo item"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 3);
+    assert_eq!(todos[2].description, "Water the plants");
+
+    // again isn't recorded itself, so the last list is still found
+    let output = run_command(&["again"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Running: todo-cli list --all"));
+
+    teardown();
+}
+
+// Private item tests
+
+#[test]
+fn test_private_items_and_redacted_list() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Buy milk", None, None),
+        make_todo("Interview at Rival Corp", None, None),
+    ]);
+
+    let output = run_command(&["private", "2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Todo item 2 is now private"));
+
+    let output = run_command(&["list", "--redact"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Buy milk"));
+    assert!(stdout.contains("2 (private)"));
+    assert!(!stdout.contains("Rival"));
+
+    let output = run_command(&["list"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Interview at Rival Corp"));
+
+    let output = run_command(&["private", "2", "--clear"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no longer private"));
+    let output = run_command(&["list", "--redact"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Rival"));
+
+    teardown();
+}
+
+// Add from file tests
+
+#[test]
+fn test_add_from_file_with_shared_metadata() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Book flights", None, None)]);
+    fs::write(
+        test_path("packing-list.txt"),
+        "# Packing list\n- [ ] Passport\n\n- Charger P:Gadgets\nSocks\n",
+    )
+    .unwrap();
+
+    let output = run_command(&[
+        "add",
+        "--from-file",
+        "packing-list.txt",
+        "--project",
+        "Travel",
+        "--tag",
+        "trip",
+        "Due:2030-06-01",
+    ]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Added 3 todo items from 'packing-list.txt'")
+    );
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 4);
+    assert_eq!(todos[1].description, "Passport");
+    assert_eq!(todos[1].project.as_deref(), Some("Travel"));
+    assert_eq!(todos[1].tags, ["trip"]);
+    assert_eq!(todos[1].due_date.as_deref(), Some("2030/06/01"));
+    // A project in the line itself wins
+    assert_eq!(todos[2].project.as_deref(), Some("Gadgets"));
+    assert_eq!(todos[3].description, "Socks");
+
+    // One bad line stops the whole file
+    fs::write(test_path("bad.txt"), "Umbrella\nRain jacket Due:someday\n").unwrap();
+    let output = run_command(&["add", "--from-file", "bad.txt"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("line 2: Invalid due date 'someday'"));
+    assert!(stderr.contains("No items were added"));
+
+    // Lines can come from stdin too
+    let output = run_command_with_input(&["add", "--from-file", "-"], "Umbrella\n");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Added 1 todo item from '-'"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 5);
+
+    teardown();
+}
+
+// Git log import tests
+
+#[test]
+fn test_import_git_log() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Book flights", None, None)]);
+    let repo = test_path("widget");
+    fs::create_dir(&repo).unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(["-c", "user.name=Dev", "-c", "user.email=dev@example.com"])
+            .args(args)
+            .output()
+            .expect("Failed to run git")
+            .status;
+        assert!(status.success());
+    };
+    git(&["init", "-q"]);
+    git(&["commit", "-q", "--allow-empty", "-m", "Initial commit"]);
+    git(&["tag", "v1.2.0"]);
+    git(&[
+        "commit",
+        "-q",
+        "--allow-empty",
+        "-m",
+        "Add export. TODO: cover the CSV path",
+    ]);
+    git(&["commit", "-q", "--allow-empty", "-m", "Tidy up"]);
+    git(&[
+        "commit",
+        "-q",
+        "--allow-empty",
+        "-m",
+        "FIXME: crash on empty input",
+    ]);
+
+    let output = run_command(&[
+        "import", "--format", "git-log", "widget", "--since", "v1.2.0",
+    ]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Imported 2 todo items from the git log of 'widget'")
+    );
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 3);
+    assert!(todos[1].description.starts_with("cover the CSV path ("));
+    assert_eq!(todos[1].tags, ["widget"]);
+    assert!(todos[2].description.starts_with("crash on empty input ("));
+
+    // Running it again adds nothing new
+    let output = run_command(&["import", "--format", "git-log", "widget"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Imported 0 todo items"));
+    assert!(stdout.contains("Skipped 2 already in the list"));
+
+    teardown();
+}
+
+// Scan tests
+
+#[test]
+fn test_scan_syncs_code_comments() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Book flights", None, None)]);
+    let code = test_path("widget");
+    fs::create_dir_all(code.join("src")).unwrap();
+    fs::create_dir_all(code.join("target")).unwrap();
+    fs::write(
+        code.join("src/lib.rs"),
+        "// TODO: cover the CSV path\nfn parse() {}\n// FIXME: crash on empty input\n",
+    )
+    .unwrap();
+    fs::write(code.join("target/build.rs"), "// TODO: generated\n").unwrap();
+
+    let output = run_command(&["scan", "widget"]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("for project 'widget': 2 added, 0 updated, 0 removed")
+    );
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 3);
+    assert_eq!(todos[1].description, "cover the CSV path");
+    assert_eq!(todos[1].project.as_deref(), Some("widget"));
+    assert_eq!(todos[2].tags, ["fixme"]);
+
+    let output = run_command(&["show", "3"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Source:      src/lib.rs:3"));
+
+    // Fixing one comment and moving the other removes and updates their items
+    fs::write(
+        code.join("src/lib.rs"),
+        "fn parse() {}\n\n// TODO: cover the CSV path\n",
+    )
+    .unwrap();
+    let output = run_command(&["scan", "widget"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("0 added, 1 updated, 1 removed"));
+    let output = run_command(&["show", "2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Source:      src/lib.rs:3"));
+
+    teardown();
+}
+
+// Repeated marker tests
+
+#[test]
+fn test_add_warns_about_repeated_markers() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+    let output = run_command(&["add", "Call Bob @phone @office"]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Warning: Ignored '@office' because the item already has a context")
+    );
+    let output = run_command(&["show", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Context:     phone"));
+
+    // --strict refuses the item instead
+    let output = run_command(&["add", "--strict", "Plan trip P:Home P:Travel"]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("Error: 'P:Travel' gives a second project; an item has only one")
+    );
+
+    // --project only fills in a project the text leaves out, so it never warns
+    let output = run_command(&["add", "--strict", "--project", "Work", "Pay rent P:Home"]);
+    assert!(output.stderr.is_empty());
+    let output = run_command(&["show", "2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Project:     Home"));
+
+    // The config can keep them in the description instead
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[metadata]\nkeep_extra_markers = true\n",
+    )
+    .unwrap();
+    let output = run_command(&["add", "Email @home @alice"]);
+    assert!(output.stderr.is_empty());
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos.len(), 3);
+    assert_eq!(todos[2].description, "Email @alice");
+    assert_eq!(todos[2].context.as_deref(), Some("home"));
+
+    teardown();
+}
+
+// Multi-item edit tests
+
+#[test]
+fn test_edit_several_items_in_turn() {
+    setup();
+
+    let mut header = make_todo("Fix header", None, None);
+    header.project = Some("Web".to_string());
+    create_test_file_with_todos(vec![
+        header,
+        make_todo("Buy milk", None, None),
+        make_todo("Fix footer", None, None),
+    ]);
+
+    // Item 1 through --filter, then item 3; six answers each
+    let output = run_command_with_input(
+        &["edit", "3", "--filter", "project:Web"],
+        "\nA\n\n\n\n\nFix the footer\n\n\n\n\n\n",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Editing todo item 1 (1 of 2):"));
+    assert!(stdout.contains("Editing todo item 3 (2 of 2):"));
+    assert!(stdout.contains("Todo items 1, 3 updated successfully"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].priority, Some('A'));
+    assert_eq!(todos[1].description, "Buy milk");
+    assert_eq!(todos[2].description, "Fix the footer");
+
+    // A missing item stops before any prompt
+    let output = run_command_with_input(&["edit", "2", "7"], "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Todo item 7 does not exist"));
+
+    teardown();
+}
+
+// Priority on add tests
+
+#[test]
+fn test_add_with_priority() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+    run_command(&["add", "(A) Renew passport"]);
+    run_command(&["add", "--pri", "B", "Book flights"]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[priority]\ndefault_priority = \"C\"\n",
+    )
+    .unwrap();
+    run_command(&["add", "Pack bags"]);
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    let priorities: Vec<Option<char>> = todos.iter().map(|todo| todo.priority).collect();
+    assert_eq!(priorities, [Some('A'), Some('B'), Some('C')]);
+    assert_eq!(todos[0].description, "Renew passport");
+
+    teardown();
+}
+
+// Done time tests
+
+#[test]
+fn test_done_records_the_time() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Pay rent", None, None),
+        make_todo("Old item", None, Some("2025/11/30")),
+    ]);
+    run_command_with_input(&["done", "1"], "Y\n");
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: serde_json::Value = serde_json::from_str(&content).unwrap();
+    let done_at = todos[0]["done_at"].as_str().unwrap();
+    assert_eq!(Some(&done_at[..10]), todos[0]["done_date"].as_str());
+    assert_eq!(done_at.len(), 19);
+    // The time an item was done before times were kept is unknown
+    assert!(todos[1]["done_at"].is_null());
+
+    let output = run_command(&["show", "1"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!(
+        "Done:        {} at {}",
+        &done_at[..10],
+        &done_at[11..16]
+    )));
+    let output = run_command(&["show", "2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Done:        2025/11/30\n"));
+
+    teardown();
+}
+
+// List --where tests
+
+#[test]
+fn test_list_where_compares_dates() {
+    setup();
+
+    let mut slow = make_todo("Migrate database", None, Some("2025/06/20"));
+    slow.start_date = "2025/06/01".to_string();
+    let mut quick = make_todo("Fix typo", None, Some("2025/06/02"));
+    quick.start_date = "2025/06/01".to_string();
+    create_test_file_with_todos(vec![slow, quick, make_todo("Buy milk", None, None)]);
+
+    let output = run_command(&["list", "--all", "--where", "done>start+14d"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Migrate database"));
+    assert!(!stdout.contains("Fix typo"));
+    assert!(!stdout.contains("Buy milk"));
+
+    let output = run_command(&["list", "--where", "due<someday"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: Invalid date 'someday'"));
+
+    teardown();
+}
+
+// Pin tests
+
+#[test]
+fn test_pinned_items_list_first() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Buy milk", Some('A'), None),
+        make_todo("Renew passport", Some('C'), None),
+    ]);
+
+    let output = run_command(&["pin", "2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Todo item 2 pinned"));
+
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pinned = stdout.find("Pinned").unwrap();
+    let passport = stdout.find("Renew passport").unwrap();
+    let milk = stdout.find("Buy milk").unwrap();
+    assert!(pinned < passport && passport < milk);
+
+    let output = run_command(&["unpin", "2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Todo item 2 unpinned"));
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Pinned"));
+    assert!(stdout.find("Buy milk").unwrap() < stdout.find("Renew passport").unwrap());
+
+    teardown();
+}
+
+// Project tree tests
+
+#[test]
+fn test_projects_tree_and_parent_filter() {
+    setup();
+
+    let mut site = make_todo("Fix the contact form", None, None);
+    site.project = Some("Work.ClientA.Website".to_string());
+    let mut invoice = make_todo("Send invoice", None, None);
+    invoice.project = Some("Work.ClientB".to_string());
+    let mut shop = make_todo("Sharpen chisels", None, None);
+    shop.project = Some("Workshop".to_string());
+    create_test_file_with_todos(vec![site, invoice, shop]);
+
+    let output = run_command(&["projects", "--tree"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  P:Work (2 open, 0 done)"));
+    assert!(stdout.contains("    ClientA (1 open, 0 done)"));
+    assert!(stdout.contains("      Website (1 open, 0 done)"));
+    assert!(stdout.contains("  P:Workshop (1 open, 0 done)"));
+
+    let output = run_command(&["list", "--where", "project:Work"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Fix the contact form"));
+    assert!(stdout.contains("Send invoice"));
+    assert!(!stdout.contains("Sharpen chisels"));
+
+    teardown();
+}
+
+// Date alias tests
+
+#[test]
+fn test_date_aliases_in_due_markers_and_filters() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[date_aliases]\nlaunch = \"2026-03-01\"\n",
+    )
+    .unwrap();
+
+    run_command(&["add", "Ship the beta due:launch"]);
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[0]["due_date"], "2026/03/01");
+
+    let output = run_command(&["list", "--where", "due=launch"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Ship the beta"));
+
+    let output = run_command(&["add", "Party due:someday"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid due date 'someday'"));
+
+    teardown();
+}
+
+// Missing field filter tests
+
+#[test]
+fn test_list_items_missing_fields() {
+    setup();
+
+    let mut filed = make_todo("Write report", Some('A'), None);
+    filed.project = Some("Work".to_string());
+    let mut homeless = make_todo("Call plumber", Some('B'), None);
+    homeless.context = Some("phone".to_string());
+    create_test_file_with_todos(vec![filed, homeless, make_todo("Buy milk", None, None)]);
+
+    let output = run_command(&["list", "--no-project"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Write report"));
+    assert!(stdout.contains("Call plumber"));
+    assert!(stdout.contains("Buy milk"));
+
+    let output = run_command(&["list", "--no-project", "--no-priority"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Call plumber"));
+    assert!(stdout.contains("Buy milk"));
+
+    let output = run_command(&["list", "--no-context", "--where", "priority:A"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Write report"));
+    assert!(!stdout.contains("Buy milk"));
+
+    teardown();
+}
+
+// Diff tests
+
+#[test]
+fn test_diff_against_another_list() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Buy milk", None, None),
+        make_todo("Write report", Some('A'), None),
+    ]);
+    let theirs = vec![
+        make_todo("Write report", Some('B'), None),
+        make_todo("Call plumber", None, None),
+    ];
+    fs::write(
+        test_path("other-todo.json"),
+        serde_json::to_string_pretty(&theirs).unwrap(),
+    )
+    .unwrap();
+
+    let output = run_command(&["diff", "other-todo.json"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Only in this list:\n  1 Buy milk"));
+    assert!(stdout.contains(":\n  2 Call plumber"));
+    assert!(stdout.contains("Changed:\n  2 Write report\n      priority: \"A\" -> \"B\""));
+
+    let output = run_command(&["diff", "other-todo.json", "--format", "json"]);
+    let changes: Vec<serde_json::Value> =
+        serde_json::from_slice(&output.stdout).expect("diff --format json prints JSON");
+    assert!(changes.iter().any(|c| c["op"] == "remove" && c["id"] == 1));
+    assert!(changes.iter().any(|c| c["op"] == "add" && c["id"] == 2));
+    assert!(
+        changes
+            .iter()
+            .any(|c| c["op"] == "update" && c["fields"]["priority"] == "B")
+    );
+
+    fs::copy(test_path(TEST_TODO_FILE), test_path("same-todo.json")).unwrap();
+    let output = run_command(&["diff", "same-todo.json"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No differences"));
+
+    teardown();
+}
+
+// Owner tests
+
+#[test]
+fn test_owned_items_and_list_mine() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+    fs::write(test_path(TEST_CONFIG_FILE), "[user]\nname = \"alice\"\n").unwrap();
+
+    run_command(&["add", "Book the plumber", "--for", "alice"]);
+    run_command(&["add", "Mow the lawn", "--for", "bob"]);
+    run_command(&["add", "Buy milk"]);
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[0]["owner"], "alice");
+    assert!(todos[2]["owner"].is_null());
+
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Owner:bob"));
+
+    let output = run_command(&["list", "--mine"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Book the plumber"));
+    assert!(!stdout.contains("Mow the lawn"));
+    assert!(!stdout.contains("Buy milk"));
+
+    let output = run_command(&["show", "2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Owner:       bob"));
+
+    teardown();
+}
+
+// Done archive tests
+
+#[test]
+fn test_done_moves_items_to_archive() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Write report", None, None),
+        make_todo("Call bank", None, None),
+        make_todo("Water plants", None, None),
+    ]);
+    fs::write(test_path(TEST_CONFIG_FILE), "[done]\nconfirm = \"never\"\n").unwrap();
+
+    let output = run_command(&["done", "2", "--archive"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Todo item 2 marked as done"));
+    assert!(stdout.contains("Moved 1 item to todo-archive.json"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    let descriptions: Vec<&str> = todos.iter().map(|t| t.description.as_str()).collect();
+    assert_eq!(descriptions, ["Write report", "Water plants"]);
+    let archived: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_ARCHIVE_FILE)).unwrap()).unwrap();
+    assert_eq!(archived.len(), 1);
+    assert_eq!(archived[0].description, "Call bank");
+    assert!(archived[0].done_date.is_some());
+
+    // With the config option every completion is archived, appending to the file
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[done]\nconfirm = \"never\"\narchive = true\n",
+    )
+    .unwrap();
+    run_command(&["done", "1"]);
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].description, "Water plants");
+    let archived: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_ARCHIVE_FILE)).unwrap()).unwrap();
+    assert_eq!(archived.len(), 2);
+    assert_eq!(archived[1].description, "Write report");
+
+    teardown();
+}
+
+#[test]
+fn test_done_archive_in_failed_batch_keeps_item() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Write report", None, None),
+        make_todo("Call bank", None, None),
+    ]);
+
+    // A later line fails, so the item stays open in the list and nothing is archived
+    let output = run_command_with_input(
+        &["batch"],
+        "done --archive 1\nexport --format taskwarrior -o missing/x.json\n",
+    );
+    assert!(!output.status.success());
+    assert!(!test_path(TEST_ARCHIVE_FILE).exists());
+    assert!(!test_path(TEST_META_FILE).exists());
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 2);
+    assert!(todos[0].done_date.is_none());
+
+    // The same with [done] archive
+    fs::write(test_path(TEST_CONFIG_FILE), "[done]\narchive = true\n").unwrap();
+    let output = run_command_with_input(
+        &["batch"],
+        "done 2\nexport --format taskwarrior -o missing/x.json\n",
+    );
+    assert!(!output.status.success());
+    assert!(!test_path(TEST_ARCHIVE_FILE).exists());
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 2);
+    assert!(todos[1].done_date.is_none());
+
+    teardown();
+}
+
+// Name normalization tests
+
+#[test]
+fn test_names_normalized_on_add_and_by_normalize() {
+    setup();
+
+    let mut web = make_todo("Fix the header", None, None);
+    web.project = Some("Web".to_string());
+    let mut lower = make_todo("Fix the footer", None, None);
+    lower.project = Some("web".to_string());
+    let mut upper = make_todo("Fix the menu", None, None);
+    upper.project = Some("Web".to_string());
+    create_test_file_with_todos(vec![web, lower, upper]);
+
+    run_command(&["project", "archive", "web"]);
+
+    let output = run_command(&["normalize", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("P:web -> P:Web"));
+    assert!(stdout.contains("Would change 1 item (2)"));
+
+    let output = run_command(&["normalize", "--yes"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Changed 1 item (2)"));
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert!(todos.iter().all(|t| t.project.as_deref() == Some("Web")));
+
+    // The archived project is renamed along with its items, so they stay hidden
+    let meta: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_META_FILE)).unwrap()).unwrap();
+    assert_eq!(meta["archived_projects"], serde_json::json!(["Web"]));
+    let output = run_command(&["list"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Fix the footer"));
+    run_command(&["project", "unarchive", "Web"]);
+
+    // With the config, new items take the spelling already in the list
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[names]\ntrim = true\ncase_insensitive = true\n",
+    )
+    .unwrap();
+    run_command(&["add", "Update the logo P:WEB T:design,"]);
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[3].project.as_deref(), Some("Web"));
+    assert_eq!(todos[3].tags, ["design"]);
+
+    let output = run_command(&["normalize"]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Project and tag names are already normalized")
+    );
+
+    teardown();
+}
+
+// Convert live list tests
+
+#[test]
+fn test_convert_guards_the_live_list() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Existing item", None, None)]);
+    create_test_txt_file("Converted item S:2025/11/29\n");
+
+    let output = run_command(&["convert", TEST_TXT_FILE]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is your live list"));
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    assert!(content.contains("Existing item"));
+
+    let output = run_command(&["convert", TEST_TXT_FILE, "--append"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("and added them to 'todo.json'"));
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    let descriptions: Vec<&str> = todos.iter().map(|t| t.description.as_str()).collect();
+    assert_eq!(descriptions, ["Existing item", "Converted item"]);
+
+    let output = run_command(&["convert", TEST_TXT_FILE, "--into-live"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Backed up the live list"));
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 1);
+    let backup = fs::read_to_string(test_path("todo.json.bak")).unwrap();
+    assert!(backup.contains("Existing item"));
+
+    // The list was written by todo-cli, so doctor doesn't report an outside change
+    let output = run_command(&["doctor"]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("outside todo-cli"));
+
+    teardown();
+}
+
+#[test]
+fn test_convert_reports_changes_to_the_live_list() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Existing item", None, None)]);
+    create_test_txt_file("Converted item S:2025/11/29\n");
+
+    // Naming the live list with -o still changes it, so the change is reported
+    let output = run_command(&[
+        "--porcelain",
+        "convert",
+        TEST_TXT_FILE,
+        "-o",
+        "./todo.json",
+        "--append",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let record: serde_json::Value = serde_json::from_str(stdout.trim()).unwrap();
+    assert_eq!(record["op"], "add");
+    assert_eq!(record["id"], 2);
+    assert_eq!(record["fields"]["description"], "Converted item");
+
+    // Replacing another file leaves the list alone, so there's nothing to report
+    let output = run_command(&[
+        "--porcelain",
+        "convert",
+        TEST_TXT_FILE,
+        "--into-live",
+        "-o",
+        "other.json",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Converted 1 todo items"));
+    assert!(test_path("other.json").exists());
+
+    teardown();
+}
+
+// Dup tests
+
+#[test]
+fn test_dup_copies_item_with_overrides() {
+    setup();
+
+    let mut original = make_todo("Migrate the billing service", Some('A'), Some(&days_ago(3)));
+    original.project = Some("Backend".to_string());
+    create_test_file_with_todos(vec![original]);
+
+    let output = run_command(&[
+        "dup",
+        "1",
+        "--description-suffix",
+        "(part 2)",
+        "--project",
+        "P:Platform",
+    ]);
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("Added todo item 2 as a copy of item 1")
+    );
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<TodoItem> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos.len(), 2);
+    assert!(todos[0].done_date.is_some());
+    assert_eq!(todos[1].description, "Migrate the billing service (part 2)");
+    assert_eq!(todos[1].priority, Some('A'));
+    assert_eq!(todos[1].project.as_deref(), Some("Platform"));
+    assert_eq!(todos[1].done_date, None);
+
+    let output = run_command(&["dup", "3"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Todo item 3 does not exist"));
+
+    teardown();
+}
+
+// Completed view tests
+
+#[test]
+fn test_completed_lists_recent_done_items() {
+    setup();
+
+    let mut site = make_todo("Site visit", None, Some(&days_ago(2)));
+    site.project = Some("Work".to_string());
+    create_test_file_with_todos(vec![
+        make_todo("Ancient report", None, Some(&days_ago(40))),
+        make_todo("Still open", None, None),
+        site,
+        make_todo("Called the bank", None, Some(&days_ago(1))),
+    ]);
+
+    let output = run_command(&["completed", "--last", "7d"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let called = stdout.find("Called the bank").unwrap();
+    let visit = stdout.find("Site visit").unwrap();
+    assert!(called < visit);
+    assert!(!stdout.contains("Ancient report"));
+    assert!(!stdout.contains("Still open"));
+    assert!(stdout.contains("2 completed items"));
+
+    let output = run_command(&["completed", "--project", "Work"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Site visit P:Work"));
+    assert!(!stdout.contains("Called the bank"));
+
+    let output = run_command(&["completed", "--last", "soon"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid age 'soon'"));
+
+    let output = run_command(&["completed", "--last", "99999999999999d"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid age '99999999999999d'"));
+
+    let output = run_command(&["suggest-someday", "--older-than", "99999999999999d"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid age"));
+
+    teardown();
+}
+
+// Long description tests
+
+#[test]
+fn test_add_moves_long_description_overflow_to_notes() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[add]\nmax_description_length = 20\n",
+    )
+    .unwrap();
+
+    let output = run_command_with_input(
+        &["add", "Call the bank about the mortgage renewal P:Home"],
+        "Y\n",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("over the limit of 20"));
+    assert!(stdout.contains("Moved the end of the description to the item's notes"));
+
+    let output = run_command_with_input(&["add", "Renew the passport before the summer"], "N\n");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Move the rest"));
+    run_command(&[
+        "add",
+        "Book a table for the anniversary dinner",
+        "--no-truncate",
+    ]);
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[0]["description"], "Call the bank about");
+    assert_eq!(todos[0]["notes"], "the mortgage renewal");
+    assert_eq!(todos[0]["project"], "Home");
+    assert_eq!(
+        todos[1]["description"],
+        "Renew the passport before the summer"
+    );
+    assert!(todos[1]["notes"].is_null());
+    assert_eq!(
+        todos[2]["description"],
+        "Book a table for the anniversary dinner"
+    );
+
+    let output = run_command(&["show", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Notes:       the mortgage renewal"));
+
+    teardown();
+}
+
+// Stats breakdown tests
+
+#[test]
+fn test_stats_by_context_and_hour() {
+    setup();
+
+    let mut sam = make_todo("Call Sam", None, None);
+    sam.context = Some("phone".to_string());
+    let mut alex = make_todo("Call Alex", None, None);
+    alex.context = Some("phone".to_string());
+    let mut shelf = make_todo("Fix shelf", None, None);
+    shelf.context = Some("home".to_string());
+    create_test_file_with_todos(vec![sam, alex, shelf]);
+    run_command_with_input(&["done", "1"], "Y\n");
+
+    let output = run_command(&["stats", "--by-context"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Context"));
+    assert!(stdout.contains("@phone"));
+    assert!(stdout.contains("@home"));
+    assert!(stdout.contains("50%"));
+    assert!(!stdout.contains("Current streak"));
+
+    let output = run_command(&["stats", "--by-hour", "--json"]);
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let hours = stats["by_hour"].as_array().unwrap();
+    assert_eq!(hours.len(), 24);
+    let total: u64 = hours.iter().map(|row| row["done"].as_u64().unwrap()).sum();
+    assert_eq!(total, 1);
+    assert!(stats.get("by_context").is_none());
+    assert_eq!(stats["completed_total"], 1);
+
+    let output = run_command(&["stats", "--by-hour"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(":00  #"));
+
+    teardown();
+}
+
+// Priority alias tests
+
+#[test]
+fn test_pr_accepts_number_and_word_aliases() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Buy milk", None, None),
+        make_todo("Send email", None, None),
+        make_todo("Water plants", None, None),
+    ]);
+
+    run_command(&["pr", "2", "1"]);
+    run_command(&["pr", "high", "2"]);
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert_eq!(todos[0]["priority"], "B");
+    assert_eq!(todos[1]["priority"], "A");
+
+    let output = run_command(&["pr", "27", "3"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Priority '27' is out of range (use A-Z); did you mean Z?"));
+
+    let output = run_command(&["pr", "hgih", "3"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("did you mean high?"));
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert!(todos[2]["priority"].is_null());
+
+    teardown();
+}
+
+// Prompt input tests
+
+#[test]
+fn test_prompts_cancel_on_eof_and_invalid_utf8() {
+    use std::io::Write;
+
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+
+    let output = run_command_with_input(&["done", "1"], "");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Cancelled"));
+
+    let mut child = Command::new(get_binary_path())
+        .current_dir(test_dir())
+        .args(["done", "1"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn command");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"\xff\xfe\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Cancelled"));
+    assert!(output.stderr.is_empty());
+
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert!(todos[0]["done_date"].is_null());
+
+    let output = run_command_with_input(&["done", "1"], "y\r\n");
+    assert!(output.status.success());
+    let content = fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap();
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+    assert!(!todos[0]["done_date"].is_null());
+
+    teardown();
+}
+
+// Shell init tests
+
+#[test]
+fn test_shell_init_prints_completions_and_binding() {
+    setup();
+
+    let output = run_command(&["shell-init", "bash"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("complete -F _todo-cli"));
+    assert!(stdout.contains("__todo_cli_add()"));
+    assert!(!test_path(TEST_TODO_FILE).exists());
+
+    let output = run_command(&["shell-init", "tcsh"]);
+    assert!(!output.status.success());
+
+    teardown();
+}
+
+// Due date shortcut tests
+
+#[test]
+fn test_list_due_shortcuts() {
+    setup();
+
+    let due = |description: &str, days: i64| {
+        let mut todo = make_todo(description, None, None);
+        todo.due_date = Some(days_ago(days));
+        todo
+    };
+    create_test_file_with_todos(vec![
+        due("Pay rent", 2),
+        due("Call bank", 0),
+        due("Book dentist", -3),
+        due("Renew passport", -30),
+        make_todo("Someday", None, None),
+    ]);
+
+    let listed = |flag: &str| {
+        let output = run_command(&["list", flag, "--oneline-ids"]);
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.split('\t').nth(1).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(listed("--overdue"), ["Pay rent"]);
+    assert_eq!(listed("--due-today"), ["Call bank"]);
+    assert_eq!(listed("--due-week"), ["Call bank", "Book dentist"]);
+
+    let output = run_command(&["list", "--overdue", "--due-today"]);
+    assert!(!output.status.success());
+
+    teardown();
+}
+
+// Escaped marker tests
+
+#[test]
+fn test_escaped_markers_are_kept_verbatim() {
+    setup();
+
+    create_test_file_with_todos(vec![]);
+    run_command(&["add", r"Email \P:Personal about \@channel @work"]);
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].description, "Email P:Personal about @channel");
+    assert_eq!(todos[0].context.as_deref(), Some("work"));
+    assert_eq!(todos[0].project, None);
+
+    let export_file = "Tasks.json";
+    run_command(&["export", "--format", "gtasks", "-o", export_file]);
+    create_test_file_with_todos(vec![]);
+    run_command(&["import", export_file]);
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].description, "Email P:Personal about @channel");
+    assert_eq!(todos[0].context.as_deref(), Some("work"));
+    assert_eq!(todos[0].project, None);
+
+    teardown();
+}
+
+// Someday tests
+
+#[test]
+fn test_suggest_someday_hides_stale_items() {
+    setup();
+
+    create_test_file_with_todos(vec![
+        make_todo("Learn the cello", None, None),
+        make_todo("Write a novel", None, None),
+    ]);
+    run_command(&["add", "Pay rent"]);
+
+    let output = run_command(&["suggest-someday", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Learn the cello"));
+    assert!(!stdout.contains("Pay rent"));
+    assert!(stdout.contains("Would suggest 2 items"));
+
+    let output = run_command_with_input(&["suggest-someday"], "Y\nN\n");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Moved 1 item to someday (1)"));
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[0].tags, vec!["someday".to_string()]);
+    assert!(todos[1].tags.is_empty());
+
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Learn the cello"));
+    assert!(stdout.contains("Write a novel"));
+
+    let output = run_command(&["list", "--someday"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Learn the cello"));
+    assert!(!stdout.contains("Write a novel"));
+
+    let output = run_command(&["list", "--all"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Learn the cello"));
+
+    // Tagging it touched the item, and it isn't suggested again
+    let output = run_command(&["suggest-someday", "--dry-run"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("Learn the cello"));
+    assert!(stdout.contains("Would suggest 1 item"));
+
+    teardown();
+}
+
+// Content hash tests
+
+#[test]
+fn test_done_by_hash_survives_reordering() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Write report", None, None)]);
+    fs::write(test_path(TEST_CONFIG_FILE), "[done]\nconfirm = \"never\"\n").unwrap();
+
+    let output = run_command(&["--porcelain", "add", "Buy milk"]);
+    let record: serde_json::Value =
+        serde_json::from_str(String::from_utf8_lossy(&output.stdout).trim()).unwrap();
+    let hash = record["hash"].as_str().unwrap().to_string();
+    assert_eq!(hash.len(), 7);
+
+    // Another item goes on top, so "Buy milk" is no longer item 2
+    run_command(&["add", "--top", "Urgent"]);
+    let output = run_command(&["done", "--hash", &hash]);
+    assert!(output.status.success());
+
+    let todos: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path(TEST_TODO_FILE)).unwrap()).unwrap();
+    assert_eq!(todos[2].description, "Buy milk");
+    assert!(todos[2].done_date.is_some());
+    assert!(todos[0].done_date.is_none());
+    assert!(todos[1].done_date.is_none());
+
+    let output = run_command(&["done", "--hash", "0000000"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No todo item has hash '0000000'"));
+
+    let output = run_command(&["show", "3"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&hash));
+
+    teardown();
+}
+
+// Profile tests
+
+#[test]
+fn test_profile_switches_file_and_list_view() {
+    setup();
+
+    create_test_file_with_todos(vec![make_todo("Buy milk", None, None)]);
+    fs::create_dir_all(test_path("work")).unwrap();
+    let mut waiting = make_todo("Chase supplier @WF", None, None);
+    waiting.context = Some("WF".to_string());
+    let work = vec![make_todo("Send invoice", None, None), waiting];
+    fs::write(
+        test_path("work/todo.json"),
+        serde_json::to_string_pretty(&work).unwrap(),
+    )
+    .unwrap();
+    fs::write(
+        test_path(TEST_CONFIG_FILE),
+        "[profiles.work]\nfile = \"work\"\nlist = { hide_waiting = true }\n",
+    )
+    .unwrap();
+
+    let output = run_command(&["--profile", "work", "list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Send invoice"));
+    assert!(!stdout.contains("Chase supplier"));
+    assert!(!stdout.contains("Buy milk"));
+
+    let output = Command::new(get_binary_path())
+        .current_dir(test_dir())
+        .env("TODO_PROFILE", "work")
+        .args(["add", "Book travel"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(output.status.success());
+    let work: Vec<TodoItem> =
+        serde_json::from_str(&fs::read_to_string(test_path("work/todo.json")).unwrap()).unwrap();
+    assert_eq!(work.len(), 3);
+    assert_eq!(work[2].description, "Book travel");
+
+    // Without a profile the default list is untouched
+    let output = run_command(&["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Buy milk"));
+    assert!(!stdout.contains("Book travel"));
+
+    let output = run_command(&["--profile", "home", "list"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No profile 'home'"));
+
+    teardown();
+}